@@ -0,0 +1,107 @@
+//! Compare the transactional compile against the old autocommit insert loop.
+//!
+//! Run with `cargo bench`. Both sides write the same synthetic posts to a
+//! database file on disk, so the fsync cost of autocommit is included.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use rusqlite::{params, Connection};
+
+use eplot_data_compiler::compile::{compile, CompileOptions};
+use eplot_data_compiler::db;
+use eplot_data_compiler::episode::parse_episode;
+use eplot_data_compiler::source::Post;
+
+const SERIES: usize = 40;
+const EPISODES_PER_SERIES: usize = 25;
+
+fn synthetic_posts() -> Vec<Post> {
+    let mut posts = Vec::new();
+    for series in 0..SERIES {
+        for ep in 1..=EPISODES_PER_SERIES {
+            let name = format!("series{:02}_{:02}.md", series, ep);
+            let content = format!(
+                "---\ntitle: \"测试番剧{} {:02}\"\ndescription: '章节标题，小节标题：正文内容{}。'\ntags: [2025{:02}, 动画]\n---\n\n正文\n",
+                series,
+                ep,
+                ep,
+                series % 12 + 1
+            );
+            posts.push(Post {
+                path: PathBuf::from(&name),
+                name,
+                content: Ok(content),
+            });
+        }
+    }
+    posts
+}
+
+// The insert phase as it was before: one autocommit statement and one
+// prepare per episode, looking the series id up in the database each time.
+fn legacy_insert(path: &Path, posts: &[Post]) {
+    let conn = Connection::open(path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE series_data (id INTEGER PRIMARY KEY AUTOINCREMENT, series_name TEXT UNIQUE,
+            series_year TEXT, series_month TEXT);
+        CREATE TABLE ep_data (id INTEGER PRIMARY KEY AUTOINCREMENT, ep_name TEXT, ep_num TEXT,
+            ep_year TEXT, ep_month TEXT, series_id INTEGER, abstract TEXT);",
+    )
+    .unwrap();
+    let episodes: Vec<_> = posts
+        .iter()
+        .map(|post| parse_episode(&post.name, post.content.as_ref().unwrap()).unwrap())
+        .collect();
+    for episode in &episodes {
+        conn.execute(
+            "INSERT OR IGNORE INTO series_data (series_name, series_year, series_month) VALUES (?1, ?2, ?3)",
+            params![episode.series_name, episode.ep_year, episode.ep_month],
+        )
+        .unwrap();
+    }
+    for episode in &episodes {
+        let mut stmt = conn.prepare("SELECT id FROM series_data WHERE series_name = ?1").unwrap();
+        let series_id: i64 = stmt.query_row(params![episode.series_name], |row| row.get(0)).unwrap();
+        conn.execute(
+            "INSERT INTO ep_data (ep_name, ep_num, ep_year, ep_month, series_id, abstract) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                episode.series_name,
+                episode.ep_label,
+                episode.ep_year,
+                episode.ep_month,
+                series_id,
+                episode.abstract_text
+            ],
+        )
+        .unwrap();
+    }
+}
+
+fn transactional_compile(path: &Path, posts: &[Post]) {
+    let conn = db::open(path).unwrap();
+    compile(&conn, posts, &CompileOptions { full: true, ..Default::default() }).unwrap();
+}
+
+fn time(dir: &Path, label: &str, run: fn(&Path, &[Post]), posts: &[Post]) -> Duration {
+    let path = dir.join(format!("{}.db", label));
+    let _ = fs::remove_file(&path);
+    let start = Instant::now();
+    run(&path, posts);
+    let elapsed = start.elapsed();
+    println!("{:<14} {:>5} episodes in {:>8.1?}", label, posts.len(), elapsed);
+    elapsed
+}
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("eplot-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let posts = synthetic_posts();
+
+    let legacy = time(&dir, "autocommit", legacy_insert, &posts);
+    let current = time(&dir, "transaction", transactional_compile, &posts);
+    println!("speedup: {:.1}x", legacy.as_secs_f64() / current.as_secs_f64());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,42 @@
+//! Render the markdown body of a post to HTML and to plain text.
+
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+
+fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_FOOTNOTES
+}
+
+pub fn render_html(markdown: &str) -> String {
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new_ext(markdown, options()));
+    out
+}
+
+/// Plain text with markup removed, keeping one blank line between blocks
+/// and one line per list item.
+pub fn render_plain(markdown: &str) -> String {
+    let mut out = String::new();
+    for event in Parser::new_ext(markdown, options()) {
+        match event {
+            Event::Text(text) | Event::Code(text) => out.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => out.push('\n'),
+            Event::Start(Tag::Item) => out.push_str("- "),
+            Event::End(TagEnd::Item) => out.push('\n'),
+            Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock | TagEnd::BlockQuote(_) | TagEnd::List(_),
+            ) => out.push_str("\n\n"),
+            Event::Rule => out.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    // Nested blocks end together; collapse the extra blank lines they leave
+    let mut plain = String::with_capacity(out.len());
+    let mut newlines = 0;
+    for c in out.trim().chars() {
+        newlines = if c == '\n' { newlines + 1 } else { 0 };
+        if newlines <= 2 {
+            plain.push(c);
+        }
+    }
+    plain
+}
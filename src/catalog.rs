@@ -0,0 +1,209 @@
+//! The compiled catalog in memory: series rows derived from their episodes.
+//!
+//! [`db::Writer`](crate::db::Writer) fills `series_data` from [`SeriesRow`], and the
+//! file exports read a whole [`Catalog`] back from the compiled database, so they see
+//! exactly the episodes, skipped duplicates and spreadsheet edits that compile stored.
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, Result};
+
+use crate::coverage::Coverage;
+use crate::episode::Episode;
+use crate::franchise::{franchise_of, split_season, FranchiseSeason};
+use crate::ids;
+use crate::overrides::Overrides;
+use crate::season::Season;
+use crate::sections::Section;
+
+/// Everything stored in a `series_data` row.
+#[derive(Debug, Clone)]
+pub struct SeriesRow {
+    pub id: i64,
+    pub series_name: String,
+    pub display_name: Option<String>,
+    pub year: Option<i64>,
+    pub month: Option<i64>,
+    pub season: Option<Season>,
+    pub franchise: FranchiseSeason,
+}
+
+impl SeriesRow {
+    /// The series of `first`, the first episode compiled for it.
+    /// The override file, or else that episode, decides the year and month.
+    pub fn new(first: &Episode, overrides: &Overrides) -> SeriesRow {
+        let fixed = overrides.series(&first.series_name);
+        let year = fixed.and_then(|fixed| fixed.year).or(first.ep_year);
+        let month = fixed.and_then(|fixed| fixed.month).or(first.ep_month);
+        SeriesRow {
+            id: ids::series_id(&first.series_name),
+            series_name: first.series_name.clone(),
+            display_name: fixed.and_then(|fixed| fixed.display_name.clone()),
+            year,
+            month,
+            season: Season::from_year_month(year, month),
+            franchise: franchise_of(&first.series_name, overrides),
+        }
+    }
+}
+
+/// One compiled episode and the source file it came from.
+#[derive(Debug, Clone)]
+pub struct CatalogEpisode {
+    pub id: i64,
+    /// Source file name, e.g. `spy_01.md`
+    pub source: String,
+    pub episode: Episode,
+}
+
+#[derive(Debug, Clone)]
+pub struct CatalogSeries {
+    pub row: SeriesRow,
+    /// Ordered by episode number, then label
+    pub episodes: Vec<CatalogEpisode>,
+    pub coverage: Coverage,
+}
+
+/// Every series in a compiled database.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    /// Ordered by series name
+    pub series: Vec<CatalogSeries>,
+}
+
+impl Catalog {
+    /// Read every series and episode back from a compiled database.
+    pub fn load(conn: &Connection) -> Result<Catalog> {
+        let mut sections: HashMap<i64, Vec<Section>> = HashMap::new();
+        let mut stmt = conn.prepare(
+            "SELECT ep_id, section_title, subsection_title, body FROM ep_sections ORDER BY ep_id, position",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            sections.entry(row.get(0)?).or_default().push(Section {
+                section_title: row.get(1)?,
+                subsection_title: row.get(2)?,
+                body: row.get(3)?,
+            });
+        }
+        let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+        let mut stmt = conn.prepare(
+            "SELECT episode_tags.ep_id, tags.name FROM episode_tags JOIN tags ON tags.id = episode_tags.tag_id
+             ORDER BY episode_tags.ep_id, episode_tags.position",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            tags.entry(row.get(0)?).or_default().push(row.get(1)?);
+        }
+
+        let mut catalog = Catalog::default();
+        let mut index: HashMap<i64, usize> = HashMap::new();
+        let mut stmt = conn.prepare(
+            "SELECT series_data.id, series_name, display_name, series_year, series_month, franchise_name, season_ordinal
+             FROM series_data JOIN franchises ON franchises.id = series_data.franchise_id
+             ORDER BY series_name",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let series_name: String = row.get(1)?;
+            let (year, month) = (row.get(3)?, row.get(4)?);
+            // An emptied ordinal falls back to the one in the name
+            let season = row.get::<_, Option<i64>>(6)?.unwrap_or_else(|| split_season(&series_name).1);
+            index.insert(row.get(0)?, catalog.series.len());
+            catalog.series.push(CatalogSeries {
+                row: SeriesRow {
+                    id: row.get(0)?,
+                    display_name: row.get(2)?,
+                    year,
+                    month,
+                    season: Season::from_year_month(year, month),
+                    franchise: FranchiseSeason {
+                        franchise_name: row.get(5)?,
+                        season,
+                    },
+                    series_name,
+                },
+                episodes: Vec::new(),
+                coverage: Coverage::default(),
+            });
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT ep_data.id, source_files.path, series_id, ep_name, raw_series_name, ep_label, ep_num,
+                    ep_year, ep_month, abstract, ep_body.markdown
+             FROM ep_data
+             JOIN source_files ON source_files.ep_id = ep_data.id
+             LEFT JOIN ep_body ON ep_body.ep_id = ep_data.id",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let Some(&idx) = index.get(&row.get::<_, i64>(2)?) else {
+                continue;
+            };
+            let series_name: String = row.get(3)?;
+            catalog.series[idx].episodes.push(CatalogEpisode {
+                id,
+                source: row.get(1)?,
+                episode: Episode {
+                    raw_series_name: row.get::<_, Option<String>>(4)?.unwrap_or_else(|| series_name.clone()),
+                    series_name,
+                    ep_label: row.get(5)?,
+                    ep_num: row.get(6)?,
+                    ep_year: row.get(7)?,
+                    ep_month: row.get(8)?,
+                    abstract_text: row.get::<_, Option<String>>(9)?.unwrap_or_default(),
+                    sections: sections.remove(&id).unwrap_or_default(),
+                    body: row.get::<_, Option<String>>(10)?.unwrap_or_default(),
+                    tags: tags.remove(&id).unwrap_or_default(),
+                },
+            });
+        }
+        catalog.update_episodes();
+        Ok(catalog)
+    }
+
+    // Order each series' episodes and work out its coverage
+    fn update_episodes(&mut self) {
+        for series in &mut self.series {
+            series.episodes.sort_by(|a, b| {
+                let (a_num, b_num) = (a.episode.ep_num.unwrap_or(f64::INFINITY), b.episode.ep_num.unwrap_or(f64::INFINITY));
+                a_num.total_cmp(&b_num).then_with(|| a.episode.ep_label.cmp(&b.episode.ep_label))
+            });
+            let nums: Vec<f64> = series.episodes.iter().filter_map(|ep| ep.episode.ep_num).collect();
+            series.coverage = Coverage::of(&nums);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::{compile, CompileOptions};
+    use crate::db;
+    use crate::source::fixtures::post;
+
+    #[test]
+    fn reads_back_edited_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        let posts = [post("胆大党_01.md", "202507"), post("胆大党_02.md", "202507")];
+        compile(&conn, &posts, &CompileOptions::default()).unwrap();
+        let series_id = ids::series_id("胆大党");
+        db::set_edit(&conn, "series_data", series_id, "series_month", Some("10")).unwrap();
+        db::set_edit(&conn, "series_data", series_id, "display_name", Some("Dandadan")).unwrap();
+        db::set_edit(&conn, "ep_data", ids::episode_id("胆大党_01.md"), "ep_num", Some("5")).unwrap();
+        db::apply_edits(&conn).unwrap();
+
+        let catalog = Catalog::load(&conn).unwrap();
+        let series = &catalog.series[0];
+        assert_eq!((series.row.year, series.row.month), (Some(2025), Some(10)));
+        assert_eq!(series.row.season.map(|season| season.id()), Some(20254));
+        assert_eq!(series.row.display_name.as_deref(), Some("Dandadan"));
+        assert_eq!(series.row.franchise, FranchiseSeason { franchise_name: "胆大党".to_string(), season: 1 });
+        let sources: Vec<&str> = series.episodes.iter().map(|ep| ep.source.as_str()).collect();
+        assert_eq!(sources, ["胆大党_02.md", "胆大党_01.md"]);
+        assert_eq!(series.coverage.gaps, [(3, 4)]);
+        assert_eq!(series.episodes[0].episode.tags, ["202507"]);
+        assert_eq!(series.episodes[0].episode.body, "正文");
+    }
+}
@@ -0,0 +1,432 @@
+//! Compile a directory of eplot markdown posts into the SQLite database.
+
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+
+use crate::coverage::{Coverage, Issue};
+use crate::episode::{parse_episode, Episode};
+use crate::history::FileHistory;
+use crate::error::{Error, Result};
+use crate::overrides::Overrides;
+use crate::source::Post;
+use crate::{db, ids, migrations};
+
+/// Settings for one compile run.
+#[derive(Debug, Default)]
+pub struct CompileOptions {
+    /// eplot commit the posts were read from, if they came from git
+    pub commit: Option<String>,
+    /// Rebuild every episode instead of only changed files
+    pub full: bool,
+    pub overrides: Overrides,
+    /// Fail instead of warning on duplicate, missing or outlying episode numbers
+    pub strict: bool,
+    /// Git history of the posts by file name, when they came from a checkout
+    pub history: Option<HashMap<String, FileHistory>>,
+}
+
+/// Row counts from one compile run.
+#[derive(Debug, Default, Clone)]
+pub struct CompileStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+    /// Files that could not be read, parsed or stored and were skipped
+    pub failed: usize,
+    /// Why each unreadable or unparsable file was skipped
+    pub errors: Vec<(PathBuf, String)>,
+    /// Schema migrations applied first, as version and description
+    pub migrated: Vec<(i64, &'static str)>,
+    /// Every episode was cleared and compiled again
+    pub rebuilt: bool,
+    /// `(alias, canonical)` pairs from the override file that matched no episode
+    pub unused_aliases: Vec<(String, String)>,
+    /// Numbering problems found in each series, including skipped duplicates, by series name
+    pub coverage_issues: Vec<(String, Issue)>,
+}
+
+/// Bring the database in line with `posts`.
+///
+/// Only files whose content hash changed are re-parsed unless `full` is set or the
+/// override file changed. All writes happen in one transaction.
+/// Returns `None` without touching anything when `commit` was already compiled.
+pub fn compile(conn: &Connection, posts: &[Post], options: &CompileOptions) -> Result<Option<CompileStats>> {
+    let migrated = migrations::migrate(conn)?;
+    let tx = conn.unchecked_transaction()?;
+    let conn = &*tx;
+    let commit = options.commit.as_deref();
+    let overrides = &options.overrides;
+
+    // Databases built before file hashes or stable ids were recorded have to be rebuilt once,
+    // and so does everything when the override file changed
+    let outdated = db::get_info(conn, "id_scheme")?.as_deref() != Some(ids::ID_SCHEME);
+    let overrides_changed = db::get_info(conn, "overrides")?.unwrap_or_default() != overrides.fingerprint();
+    // A migrated database has to be written back even when the posts are unchanged
+    if migrated.is_empty()
+        && !options.full
+        && !outdated
+        && !overrides_changed
+        && commit.is_some()
+        && db::get_info(conn, "eplot_commit")?.as_deref() == commit
+    {
+        return Ok(None);
+    }
+
+    let rebuilt = options.full || ((outdated || overrides_changed) && db::episode_count(conn)? > 0);
+    if rebuilt {
+        db::clear(conn)?;
+    }
+    if outdated {
+        db::set_info(conn, "id_scheme", ids::ID_SCHEME)?;
+    }
+    db::set_info(conn, "overrides", overrides.fingerprint())?;
+    let mut known = db::source_hashes(conn)?;
+    let mut writer = db::Writer::new(conn, overrides)?;
+
+    let mut stats = CompileStats {
+        migrated,
+        rebuilt,
+        ..CompileStats::default()
+    };
+    // Series whose first episode may have changed, by name
+    let mut touched = BTreeSet::new();
+    // Posts whose label another post of their series holds, settled once the rest are written
+    let mut contested = Vec::new();
+    for post in posts {
+        let content = match &post.content {
+            Ok(content) => content,
+            Err(err) => {
+                stats.errors.push((post.path.clone(), err.clone()));
+                known.remove(&post.name);
+                stats.failed += 1;
+                continue;
+            }
+        };
+        let hash = ids::content_hash(content);
+        let previous = known.remove(&post.name);
+        if previous.as_deref() == Some(hash.as_str()) {
+            stats.unchanged += 1;
+            continue;
+        }
+        let mut episode = match parse_episode(&post.name, content) {
+            Ok(episode) => episode,
+            Err(err) => {
+                // Keep the previously compiled row until the file is fixed
+                stats.errors.push((post.path.clone(), err.to_string()));
+                stats.failed += 1;
+                continue;
+            }
+        };
+        episode.series_name = overrides.canonical(&episode.raw_series_name).to_string();
+        let exists = previous.is_some();
+        if db::label_owner(conn, &episode.series_name, &episode.ep_label, &post.name)?.is_some() {
+            contested.push((post, hash, episode, exists));
+            continue;
+        }
+        if exists {
+            touched.extend(db::episode_series(conn, &post.name)?);
+        }
+        touched.insert(episode.series_name.clone());
+        store(&mut writer, post, &hash, &episode, exists)?;
+        if exists {
+            stats.updated += 1;
+        } else {
+            stats.added += 1;
+        }
+    }
+
+    // Whatever is left in `known` was deleted from the blog
+    for path in known.keys() {
+        touched.extend(db::episode_series(conn, path)?);
+        writer.delete_episode(path)?;
+    }
+    stats.removed = known.len();
+    // Of two posts with the same label the one that sorts first wins, as in a full compile,
+    // and the other is left out of the database so it's tried again next time
+    for (post, hash, episode, exists) in contested {
+        if exists {
+            touched.extend(db::episode_series(conn, &post.name)?);
+        }
+        touched.insert(episode.series_name.clone());
+        if let Some(owner) = db::label_owner(conn, &episode.series_name, &episode.ep_label, &post.name)? {
+            let (kept, skipped) = if owner < post.name {
+                (owner, post.name.clone())
+            } else {
+                (post.name.clone(), owner)
+            };
+            touched.extend(db::episode_series(conn, &skipped)?);
+            writer.delete_episode(&skipped)?;
+            stats.failed += 1;
+            let lost = skipped == post.name;
+            let issue = Issue::DuplicateLabel {
+                label: episode.ep_label.clone(),
+                kept,
+                skipped,
+            };
+            stats.coverage_issues.push((episode.series_name.clone(), issue));
+            if lost {
+                continue;
+            }
+        }
+        store(&mut writer, post, &hash, &episode, exists)?;
+        if exists {
+            stats.updated += 1;
+        } else {
+            stats.added += 1;
+        }
+    }
+    // A series row is inserted from its first episode and otherwise left alone,
+    // so redo it wherever that episode may have changed or gone
+    let by_name: HashMap<&str, &Post> = posts.iter().map(|post| (post.name.as_str(), post)).collect();
+    for series_name in &touched {
+        let Some(path) = db::first_source(conn, series_name)? else {
+            continue;
+        };
+        let Some(Ok(content)) = by_name.get(path.as_str()).map(|post| &post.content) else {
+            continue;
+        };
+        if let Ok(mut first) = parse_episode(&path, content) {
+            first.series_name = overrides.canonical(&first.raw_series_name).to_string();
+            writer.refresh_series(&first)?;
+        }
+    }
+    if let Some(history) = &options.history {
+        for post in posts {
+            db::set_file_history(conn, &post.name, history.get(&post.name))?;
+        }
+    }
+    db::apply_edits(conn)?;
+    db::delete_empty_series(conn)?;
+    db::delete_unused_franchises(conn)?;
+    db::delete_unused_tags(conn)?;
+    db::delete_unused_seasons(conn)?;
+    stats.coverage_issues.extend(update_coverage(conn)?);
+    if options.strict && !stats.coverage_issues.is_empty() {
+        let issues = stats.coverage_issues.iter().map(|(series, issue)| format!("{}: {}", series, issue));
+        return Err(Error::Coverage(issues.collect()));
+    }
+    if let Some(commit) = commit {
+        db::set_info(conn, "eplot_commit", commit)?;
+    }
+    let used = db::raw_series_names(conn)?;
+    stats.unused_aliases = overrides
+        .aliases()
+        .into_iter()
+        .filter(|(alias, _)| !used.contains(*alias))
+        .map(|(alias, canonical)| (alias.to_string(), canonical.to_string()))
+        .collect();
+    tx.commit()?;
+    Ok(Some(stats))
+}
+
+fn store(writer: &mut db::Writer, post: &Post, hash: &str, episode: &Episode, exists: bool) -> Result<()> {
+    writer
+        .upsert_episode(&post.name, hash, episode, exists)
+        .map_err(|source| Error::Post {
+            path: post.path.clone(),
+            source,
+        })
+}
+
+// Store each series' expected and observed episode counts and return its numbering problems
+fn update_coverage(conn: &Connection) -> Result<Vec<(String, Issue)>> {
+    let mut issues = Vec::new();
+    for (series_id, series_name, nums) in db::episode_numbers(conn)? {
+        let coverage = Coverage::of(&nums);
+        db::set_coverage(conn, series_id, &coverage)?;
+        issues.extend(coverage.issues().into_iter().map(|issue| (series_name.clone(), issue)));
+    }
+    Ok(issues)
+}
+
+/// Compile into a temporary copy of the database at `db_path`, verify it and rename it
+/// over the original, so readers only ever see the old or the finished database.
+pub fn compile_atomic(db_path: &Path, posts: &[Post], options: &CompileOptions) -> Result<Option<CompileStats>> {
+    let mut tmp_name = OsString::from(db_path.as_os_str());
+    tmp_name.push(format!(".tmp-{}", std::process::id()));
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let result = compile_into_copy(db_path, &tmp_path, posts, options);
+    match &result {
+        Ok(Some(_)) => fs::rename(&tmp_path, db_path).map_err(|err| Error::io(db_path, err))?,
+        // Nothing to publish, or a failed build: the original stays untouched
+        _ => {
+            let _ = fs::remove_file(&tmp_path);
+        }
+    }
+    result
+}
+
+fn compile_into_copy(
+    db_path: &Path,
+    tmp_path: &Path,
+    posts: &[Post],
+    options: &CompileOptions,
+) -> Result<Option<CompileStats>> {
+    if db_path.exists() {
+        fs::copy(db_path, tmp_path).map_err(|err| Error::io(tmp_path, err))?;
+    }
+    // The connection is dropped before the rename so everything is flushed
+    let conn = db::open(tmp_path)?;
+    let stats = compile(&conn, posts, options)?;
+    if stats.is_some() {
+        verify(&conn)?;
+    }
+    Ok(stats)
+}
+
+/// Run SQLite's integrity, foreign key and search index checks over a compiled database.
+pub fn verify(conn: &Connection) -> Result<()> {
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(Error::Verify(format!("integrity check failed: {}", integrity)));
+    }
+    let violations: i64 = conn.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| row.get(0))?;
+    if violations > 0 {
+        return Err(Error::Verify(format!("{} foreign key violations", violations)));
+    }
+    // Fails if the search index drifted from ep_data
+    conn.execute("INSERT INTO ep_search (ep_search, rank) VALUES ('integrity-check', 1)", [])
+        .map_err(|err| Error::Verify(format!("search index check failed: {}", err)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::fixtures::post;
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        conn
+    }
+
+    // Name, year, month and season of every series
+    type SeriesDates = (String, Option<i64>, Option<i64>, Option<i64>);
+
+    fn series_rows(conn: &Connection) -> Vec<SeriesDates> {
+        let mut stmt = conn
+            .prepare("SELECT series_name, series_year, series_month, season_id FROM series_data ORDER BY series_name")
+            .unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))).unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    // Compile `before` and then `after` incrementally, and `after` from scratch
+    fn incremental_and_full(before: &[Post], after: &[Post]) -> (Connection, Connection) {
+        let incremental = open();
+        compile(&incremental, before, &CompileOptions::default()).unwrap();
+        compile(&incremental, after, &CompileOptions::default()).unwrap();
+        let full = open();
+        compile(&full, after, &CompileOptions::default()).unwrap();
+        (incremental, full)
+    }
+
+    fn sources(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn.prepare("SELECT path FROM source_files ORDER BY path").unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn skips_posts_whose_label_is_taken() {
+        let posts = [post("a_01.md", "202507"), post("b_01.md", "202507"), post("b_02.md", "202507")];
+        let conn = open();
+        let stats = compile(&conn, &posts, &CompileOptions::default()).unwrap().unwrap();
+        assert_eq!(sources(&conn), ["a_01.md", "b_02.md"]);
+        assert_eq!((stats.added, stats.failed), (2, 1));
+        let issue = Issue::DuplicateLabel {
+            label: "01".to_string(),
+            kept: "a_01.md".to_string(),
+            skipped: "b_01.md".to_string(),
+        };
+        assert_eq!(stats.coverage_issues, [("胆大党".to_string(), issue)]);
+
+        let strict = CompileOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(compile(&open(), &posts, &strict), Err(Error::Coverage(_))));
+    }
+
+    #[test]
+    fn incremental_compile_settles_duplicates_like_a_full_one() {
+        let before = [post("b_01.md", "202507")];
+        let after = [post("a_01.md", "202510"), post("b_01.md", "202507")];
+        let (incremental, full) = incremental_and_full(&before, &after);
+        assert_eq!(sources(&incremental), ["a_01.md"]);
+        assert_eq!(series_rows(&incremental), series_rows(&full));
+
+        // Once the winner is gone the skipped post is compiled
+        compile(&incremental, &after[1..], &CompileOptions::default()).unwrap();
+        assert_eq!(sources(&incremental), ["b_01.md"]);
+    }
+
+    #[test]
+    fn incremental_compile_refreshes_changed_series() {
+        let before = [post("胆大党_01.md", "202507"), post("胆大党_02.md", "202507")];
+        let after = [post("胆大党_01.md", "202510"), post("胆大党_02.md", "202510")];
+        let (incremental, full) = incremental_and_full(&before, &after);
+        assert_eq!(series_rows(&incremental), [("胆大党".to_string(), Some(2025), Some(10), Some(20254))]);
+        assert_eq!(series_rows(&incremental), series_rows(&full));
+    }
+
+    #[test]
+    fn incremental_compile_refreshes_series_after_deleting_its_first_episode() {
+        let before = [post("胆大党_01.md", "202504"), post("胆大党_02.md", "202507")];
+        let after = [post("胆大党_02.md", "202507")];
+        let (incremental, full) = incremental_and_full(&before, &after);
+        assert_eq!(series_rows(&incremental), [("胆大党".to_string(), Some(2025), Some(7), Some(20253))]);
+        assert_eq!(series_rows(&incremental), series_rows(&full));
+    }
+
+    #[test]
+    fn stores_file_history_only_when_it_changed() {
+        let conn = open();
+        let history = FileHistory {
+            first_commit_at: 1_700_000_000,
+            first_author: "Alice".to_string(),
+            last_commit_at: 1_700_000_100,
+            last_author: "Bob".to_string(),
+        };
+        let options = CompileOptions {
+            history: Some(HashMap::from([("胆大党_01.md".to_string(), history.clone())])),
+            ..CompileOptions::default()
+        };
+        compile(&conn, &[post("胆大党_01.md", "202410")], &options).unwrap();
+        let stored: (i64, String) = conn
+            .query_row("SELECT last_commit_at, last_author FROM ep_data", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(stored, (1_700_000_100, "Bob".to_string()));
+
+        db::set_file_history(&conn, "胆大党_01.md", Some(&history)).unwrap();
+        assert_eq!(conn.changes(), 0);
+        db::set_file_history(&conn, "胆大党_01.md", None).unwrap();
+        assert_eq!(conn.changes(), 1);
+    }
+
+    #[test]
+    fn reports_migrations_rebuilds_and_unreadable_posts() {
+        let conn = open();
+        let mut broken = post("胆大党_02.md", "202410");
+        broken.content = Err("failed to read: denied".to_string());
+        let posts = [post("胆大党_01.md", "202410"), broken];
+        let stats = compile(&conn, &posts, &CompileOptions::default()).unwrap().unwrap();
+        assert_eq!(stats.migrated.len() as i64, migrations::SCHEMA_VERSION);
+        assert!(!stats.rebuilt);
+        assert_eq!(stats.errors, [(PathBuf::from("胆大党_02.md"), "failed to read: denied".to_string())]);
+
+        let full = CompileOptions {
+            full: true,
+            ..CompileOptions::default()
+        };
+        let stats = compile(&conn, &posts, &full).unwrap().unwrap();
+        assert!(stats.migrated.is_empty() && stats.rebuilt);
+    }
+}
@@ -0,0 +1,167 @@
+//! Episode coverage of a series: which numbers are there, missing, repeated or out of place.
+//!
+//! Later seasons often continue the numbering (`咒术回战 第二季` starts at 25), so the
+//! expected run goes from the lowest to the highest whole episode number rather than
+//! from 1. Specials such as `11.5` are neither expected nor counted.
+//!
+//! Two posts with the same episode label in one series can't both be stored; compile
+//! keeps the one whose file name sorts first and reports the other as an [`Issue::DuplicateLabel`].
+
+use std::fmt;
+
+// Episode numbers further than this from every other one are treated as typos, like `55` for `05`
+const OUTLIER_JUMP: f64 = 12.0;
+
+/// Episode numbering of one series.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    /// Whole episode numbers in the expected run that have at least one post
+    pub observed: i64,
+    /// Length of the run from the lowest to the highest whole episode number
+    pub expected: i64,
+    /// Episode numbers compiled from more than one post
+    pub duplicates: Vec<f64>,
+    /// Missing runs inside the expected range, inclusive
+    pub gaps: Vec<(i64, i64)>,
+    /// Whole numbers far away from the rest of the series, left out of the run
+    pub outliers: Vec<f64>,
+}
+
+/// One problem in a series' numbering.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    Duplicate(f64),
+    /// Posts with the same label, and the one of them that was skipped
+    DuplicateLabel {
+        label: String,
+        kept: String,
+        skipped: String,
+    },
+    Gap(i64, i64),
+    Outlier(f64),
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Duplicate(num) => write!(f, "episode {} appears more than once", num),
+            Issue::DuplicateLabel { label, kept, skipped } => {
+                write!(f, "episode {} of {} is also in {}, which was skipped", label, kept, skipped)
+            }
+            Issue::Gap(from, to) if from == to => write!(f, "episode {} is missing", from),
+            Issue::Gap(from, to) => write!(f, "episodes {} to {} are missing", from, to),
+            Issue::Outlier(num) => write!(f, "episode {} is far from the other episode numbers", num),
+        }
+    }
+}
+
+impl Coverage {
+    /// Work out coverage from every numeric episode number of a series, in any order.
+    pub fn of(nums: &[f64]) -> Coverage {
+        let mut nums: Vec<f64> = nums.iter().copied().filter(|num| num.is_finite()).collect();
+        nums.sort_by(f64::total_cmp);
+        let mut duplicates: Vec<f64> = nums.windows(2).filter(|pair| pair[0] == pair[1]).map(|pair| pair[0]).collect();
+        duplicates.dedup();
+        let mut whole: Vec<f64> = nums.into_iter().filter(|num| num.fract() == 0.0).collect();
+        whole.dedup();
+
+        // Split into clusters at big jumps; the biggest cluster is the series, the rest are outliers
+        let mut clusters: Vec<&[f64]> = Vec::new();
+        let mut start = 0;
+        for idx in 1..=whole.len() {
+            if idx == whole.len() || whole[idx] - whole[idx - 1] > OUTLIER_JUMP {
+                clusters.push(&whole[start..idx]);
+                start = idx;
+            }
+        }
+        // Too few episodes to tell which side is wrong
+        let main = if whole.len() < 3 {
+            &whole[..]
+        } else {
+            clusters.iter().copied().rev().max_by_key(|cluster| cluster.len()).unwrap_or(&[])
+        };
+        let outliers = whole.iter().copied().filter(|num| !main.contains(num)).collect();
+
+        let mut gaps = Vec::new();
+        for pair in main.windows(2) {
+            if pair[1] - pair[0] > 1.0 {
+                gaps.push((pair[0] as i64 + 1, pair[1] as i64 - 1));
+            }
+        }
+        let expected = match (main.first(), main.last()) {
+            (Some(&first), Some(&last)) => (last - first) as i64 + 1,
+            _ => 0,
+        };
+        Coverage {
+            observed: main.len() as i64,
+            expected,
+            duplicates,
+            gaps,
+            outliers,
+        }
+    }
+
+    pub fn issues(&self) -> Vec<Issue> {
+        let duplicates = self.duplicates.iter().map(|&num| Issue::Duplicate(num));
+        let gaps = self.gaps.iter().map(|&(from, to)| Issue::Gap(from, to));
+        let outliers = self.outliers.iter().map(|&num| Issue::Outlier(num));
+        duplicates.chain(gaps).chain(outliers).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_a_complete_run() {
+        let coverage = Coverage::of(&[3.0, 1.0, 2.0, 11.5]);
+        assert_eq!(
+            coverage,
+            Coverage {
+                observed: 3,
+                expected: 3,
+                ..Default::default()
+            }
+        );
+        assert!(coverage.issues().is_empty());
+    }
+
+    #[test]
+    fn finds_gaps_and_duplicates() {
+        let coverage = Coverage::of(&[1.0, 2.0, 3.0, 4.0, 5.0, 7.0, 5.0, 10.0]);
+        assert_eq!((coverage.observed, coverage.expected), (7, 10));
+        assert_eq!(coverage.duplicates, [5.0]);
+        assert_eq!(coverage.gaps, [(6, 6), (8, 9)]);
+        assert_eq!(
+            coverage.issues(),
+            [Issue::Duplicate(5.0), Issue::Gap(6, 6), Issue::Gap(8, 9)]
+        );
+        assert_eq!(coverage.issues()[2].to_string(), "episodes 8 to 9 are missing");
+    }
+
+    #[test]
+    fn leaves_out_typo_outliers() {
+        // `55` for `05`
+        let coverage = Coverage::of(&[1.0, 2.0, 3.0, 4.0, 55.0, 6.0]);
+        assert_eq!(coverage.outliers, [55.0]);
+        assert_eq!(coverage.gaps, [(5, 5)]);
+        assert_eq!((coverage.observed, coverage.expected), (5, 6));
+    }
+
+    #[test]
+    fn starts_later_seasons_at_their_first_episode() {
+        // 咒术回战 第二季 continues the numbering at 25
+        let coverage = Coverage::of(&[25.0, 26.0, 27.0]);
+        assert_eq!((coverage.observed, coverage.expected), (3, 3));
+        assert!(coverage.outliers.is_empty());
+    }
+
+    #[test]
+    fn needs_three_episodes_to_call_an_outlier() {
+        let coverage = Coverage::of(&[1.0, 55.0]);
+        assert!(coverage.outliers.is_empty());
+        assert_eq!(coverage.expected, 55);
+        assert_eq!(Coverage::of(&[]), Coverage::default());
+    }
+}
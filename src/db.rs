@@ -0,0 +1,469 @@
+//! Row-level database operations used by incremental compiles.
+//!
+//! The schema itself lives in [`crate::migrations`].
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+
+use crate::catalog::SeriesRow;
+use crate::coverage::Coverage;
+use crate::episode::Episode;
+use crate::history::FileHistory;
+use crate::overrides::Overrides;
+use crate::season::Season;
+use crate::{body, ids, tags};
+
+/// Open the database with foreign key enforcement turned on.
+pub fn open(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    Ok(conn)
+}
+
+/// Empty every compiled table so the next compile starts from scratch.
+pub fn clear(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM ep_sections", [])?;
+    conn.execute("DELETE FROM ep_body", [])?;
+    conn.execute("DELETE FROM episode_tags", [])?;
+    conn.execute("DELETE FROM tags", [])?;
+    conn.execute("DELETE FROM ep_data", [])?;
+    conn.execute("DELETE FROM series_data", [])?;
+    conn.execute("DELETE FROM franchises", [])?;
+    conn.execute("DELETE FROM seasons", [])?;
+    conn.execute("DELETE FROM source_files", [])?;
+    conn.execute("DELETE FROM build_info", [])?;
+    set_info(conn, "id_scheme", ids::ID_SCHEME)
+}
+
+pub fn get_info(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row("SELECT value FROM build_info WHERE key = ?1", params![key], |row| row.get(0))
+        .optional()
+}
+
+pub fn set_info(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO build_info (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+/// Content hash recorded for each source file at the last compile.
+pub fn source_hashes(conn: &Connection) -> Result<HashMap<String, String>> {
+    let mut stmt = conn.prepare("SELECT path, hash FROM source_files")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn episode_count(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM ep_data", [], |row| row.get(0))
+}
+
+/// Writes compiled episodes through cached statements, remembering which series
+/// already have a row so each one is only inserted once per compile.
+pub struct Writer<'conn> {
+    conn: &'conn Connection,
+    overrides: &'conn Overrides,
+    series: HashMap<String, i64>,
+    /// Seasons already written during this compile
+    seasons: HashSet<i64>,
+    /// Franchises already written during this compile
+    franchises: HashSet<i64>,
+    /// When this compile started, in Unix seconds
+    now: i64,
+}
+
+impl<'conn> Writer<'conn> {
+    pub fn new(conn: &'conn Connection, overrides: &'conn Overrides) -> Result<Self> {
+        let mut stmt = conn.prepare("SELECT series_name, id FROM series_data")?;
+        let series = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_>>()?;
+        Ok(Writer {
+            conn,
+            overrides,
+            series,
+            seasons: HashSet::new(),
+            franchises: HashSet::new(),
+            now: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs() as i64),
+        })
+    }
+
+    fn series_id(&mut self, episode: &Episode) -> Result<i64> {
+        if let Some(&id) = self.series.get(&episode.series_name) {
+            return Ok(id);
+        }
+        // An id collision between two names fails on the primary key instead of merging them
+        let row = SeriesRow::new(episode, self.overrides);
+        let season_id = self.season_id(row.season)?;
+        let franchise_id = self.franchise_id(&row.franchise.franchise_name)?;
+        self.conn
+            .prepare_cached(
+                "INSERT INTO series_data (id, series_name, series_year, series_month, season_id, display_name,
+                 franchise_id, season_ordinal)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?
+            .execute(params![
+                row.id,
+                row.series_name,
+                row.year,
+                row.month,
+                season_id,
+                row.display_name,
+                franchise_id,
+                row.franchise.season
+            ])?;
+        self.series.insert(row.series_name, row.id);
+        Ok(row.id)
+    }
+
+    /// Rewrite the series row of `first`, the first remaining episode of its series,
+    /// the way a full compile would have inserted it.
+    pub fn refresh_series(&mut self, first: &Episode) -> Result<()> {
+        let row = SeriesRow::new(first, self.overrides);
+        let season_id = self.season_id(row.season)?;
+        let franchise_id = self.franchise_id(&row.franchise.franchise_name)?;
+        self.conn
+            .prepare_cached(
+                "UPDATE series_data SET series_year = ?2, series_month = ?3, season_id = ?4, display_name = ?5,
+                 franchise_id = ?6, season_ordinal = ?7 WHERE id = ?1",
+            )?
+            .execute(params![
+                row.id,
+                row.year,
+                row.month,
+                season_id,
+                row.display_name,
+                franchise_id,
+                row.franchise.season
+            ])?;
+        Ok(())
+    }
+
+    fn season_id(&mut self, season: Option<Season>) -> Result<Option<i64>> {
+        let Some(season) = season else {
+            return Ok(None);
+        };
+        if self.seasons.insert(season.id()) {
+            insert_season(self.conn, season)?;
+        }
+        Ok(Some(season.id()))
+    }
+
+    fn franchise_id(&mut self, franchise_name: &str) -> Result<i64> {
+        let id = ids::franchise_id(franchise_name);
+        if self.franchises.insert(id) {
+            self.conn
+                .prepare_cached("INSERT INTO franchises (id, franchise_name) VALUES (?1, ?2) ON CONFLICT(id) DO NOTHING")?
+                .execute(params![id, franchise_name])?;
+        }
+        Ok(id)
+    }
+
+    /// Insert the episode compiled from `path`, or update it in place if `exists`,
+    /// keeping its id either way.
+    pub fn upsert_episode(&mut self, path: &str, hash: &str, episode: &Episode, exists: bool) -> Result<()> {
+        let series_id = self.series_id(episode)?;
+        let season_id = self.season_id(Season::from_year_month(episode.ep_year, episode.ep_month))?;
+        let ep_id = ids::episode_id(path);
+        if exists {
+            self.conn
+                .prepare_cached(
+                    "UPDATE ep_data SET ep_name = ?2, ep_label = ?3, ep_num = ?4, ep_year = ?5, ep_month = ?6,
+                     series_id = ?7, abstract = ?8, season_id = ?9, raw_series_name = ?10 WHERE id = ?1",
+                )?
+                .execute(params![
+                    ep_id,
+                    episode.series_name,
+                    episode.ep_label,
+                    episode.ep_num,
+                    episode.ep_year,
+                    episode.ep_month,
+                    series_id,
+                    episode.abstract_text,
+                    season_id,
+                    episode.raw_series_name
+                ])?;
+            self.conn
+                .prepare_cached("UPDATE source_files SET hash = ?2 WHERE path = ?1")?
+                .execute(params![path, hash])?;
+        } else {
+            self.conn
+                .prepare_cached(
+                    "INSERT INTO ep_data (id, ep_name, ep_label, ep_num, ep_year, ep_month, series_id, abstract,
+                     season_id, raw_series_name)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                )?
+                .execute(params![
+                    ep_id,
+                    episode.series_name,
+                    episode.ep_label,
+                    episode.ep_num,
+                    episode.ep_year,
+                    episode.ep_month,
+                    series_id,
+                    episode.abstract_text,
+                    season_id,
+                    episode.raw_series_name
+                ])?;
+            self.conn
+                .prepare_cached("INSERT INTO source_files (path, hash, ep_id) VALUES (?1, ?2, ?3)")?
+                .execute(params![path, hash, ep_id])?;
+        }
+        // Rebuilds write unchanged files again; only a new hash counts as a change
+        self.conn
+            .prepare_cached(
+                "INSERT INTO episode_history (ep_id, hash, first_seen, last_changed) VALUES (?1, ?2, ?3, ?3)
+                 ON CONFLICT(ep_id) DO UPDATE SET hash = excluded.hash, last_changed = excluded.last_changed
+                 WHERE hash != excluded.hash",
+            )?
+            .execute(params![ep_id, hash, self.now])?;
+        self.replace_sections(ep_id, episode)?;
+        self.replace_tags(ep_id, episode)?;
+        self.conn
+            .prepare_cached(
+                "INSERT INTO ep_body (ep_id, markdown, html, plain_text) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(ep_id) DO UPDATE SET
+                    markdown = excluded.markdown, html = excluded.html, plain_text = excluded.plain_text",
+            )?
+            .execute(params![
+                ep_id,
+                episode.body,
+                body::render_html(&episode.body),
+                body::render_plain(&episode.body)
+            ])?;
+        Ok(())
+    }
+
+    fn replace_sections(&mut self, ep_id: i64, episode: &Episode) -> Result<()> {
+        self.conn
+            .prepare_cached("DELETE FROM ep_sections WHERE ep_id = ?1")?
+            .execute(params![ep_id])?;
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT INTO ep_sections (ep_id, position, section_title, subsection_title, body)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for (position, section) in episode.sections.iter().enumerate() {
+            stmt.execute(params![
+                ep_id,
+                position as i64,
+                section.section_title,
+                section.subsection_title,
+                section.body
+            ])?;
+        }
+        Ok(())
+    }
+
+    fn replace_tags(&mut self, ep_id: i64, episode: &Episode) -> Result<()> {
+        self.conn
+            .prepare_cached("DELETE FROM episode_tags WHERE ep_id = ?1")?
+            .execute(params![ep_id])?;
+        for (position, tag) in episode.tags.iter().enumerate() {
+            let tag_id = ids::tag_id(tag);
+            self.conn
+                .prepare_cached("INSERT INTO tags (id, name, kind) VALUES (?1, ?2, ?3) ON CONFLICT(id) DO NOTHING")?
+                .execute(params![tag_id, tag, tags::classify(tag).as_str()])?;
+            self.conn
+                .prepare_cached("INSERT INTO episode_tags (ep_id, tag_id, position) VALUES (?1, ?2, ?3)")?
+                .execute(params![ep_id, tag_id, position as i64])?;
+        }
+        Ok(())
+    }
+
+    /// Remove the episode compiled from a source file that no longer exists.
+    pub fn delete_episode(&mut self, path: &str) -> Result<()> {
+        // source_files follows through ON DELETE CASCADE
+        self.conn
+            .prepare_cached("DELETE FROM ep_data WHERE id = ?1")?
+            .execute(params![ids::episode_id(path)])?;
+        Ok(())
+    }
+}
+
+fn insert_season(conn: &Connection, season: Season) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO seasons (id, year, quarter, start_month, name_en, name_zh)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT(id) DO NOTHING",
+    )?
+    .execute(params![
+        season.id(),
+        season.year,
+        season.quarter,
+        season.start_month(),
+        season.name_en(),
+        season.name_zh()
+    ])?;
+    Ok(())
+}
+
+/// Columns a spreadsheet edit may change, with the year and month columns that decide each table's season.
+pub const EDITABLE: &[(&str, &[&str], (&str, &str))] = &[
+    (
+        "series_data",
+        &["display_name", "series_year", "series_month", "season_ordinal"],
+        ("series_year", "series_month"),
+    ),
+    ("ep_data", &["ep_num", "ep_year", "ep_month"], ("ep_year", "ep_month")),
+];
+
+/// Record an edit, replacing an earlier one of the same cell. `None` stores an empty cell.
+pub fn set_edit(conn: &Connection, table: &str, row_id: i64, column: &str, value: Option<&str>) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT INTO edits (table_name, row_id, column_name, value) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(table_name, row_id, column_name) DO UPDATE SET value = excluded.value",
+    )?
+    .execute(params![table, row_id, column, value])?;
+    Ok(())
+}
+
+/// Write every recorded edit over the compiled rows and move edited rows to their new season.
+/// Edits of rows that no longer exist are kept but change nothing. Returns the number of cells changed.
+pub fn apply_edits(conn: &Connection) -> Result<usize> {
+    let mut changed = 0;
+    for &(table, columns, (year_column, month_column)) in EDITABLE {
+        let mut stmt = conn.prepare("SELECT row_id, column_name, value FROM edits WHERE table_name = ?1")?;
+        let edits = stmt
+            .query_map(params![table], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?)))?
+            .collect::<Result<Vec<_>>>()?;
+        let mut edited = HashSet::new();
+        for (row_id, column, value) in edits {
+            // Column names can't be bound as parameters, so only known ones reach the SQL
+            let Some(column) = columns.iter().find(|&&known| known == column) else {
+                continue;
+            };
+            let sql = format!("UPDATE {} SET {} = ?2 WHERE id = ?1", table, column);
+            let rows = conn.prepare_cached(&sql)?.execute(params![row_id, value])?;
+            if rows > 0 {
+                changed += rows;
+                edited.insert(row_id);
+            }
+        }
+        let sql = format!("SELECT {}, {} FROM {} WHERE id = ?1", year_column, month_column, table);
+        let update = format!("UPDATE {} SET season_id = ?2 WHERE id = ?1", table);
+        for row_id in edited {
+            let (year, month) = conn.query_row(&sql, params![row_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            let season = Season::from_year_month(year, month);
+            if let Some(season) = season {
+                insert_season(conn, season)?;
+            }
+            conn.execute(&update, params![row_id, season.map(Season::id)])?;
+        }
+    }
+    Ok(changed)
+}
+
+/// Store the git history of the episode compiled from `path`, or clear it when the file has none.
+/// Rows whose history is unchanged are left alone, so they don't rewrite the search index.
+pub fn set_file_history(conn: &Connection, path: &str, history: Option<&FileHistory>) -> Result<()> {
+    conn.prepare_cached(
+        "UPDATE ep_data SET first_commit_at = ?2, first_author = ?3, last_commit_at = ?4, last_author = ?5
+         WHERE id = ?1
+           AND (first_commit_at IS NOT ?2 OR first_author IS NOT ?3 OR last_commit_at IS NOT ?4 OR last_author IS NOT ?5)",
+    )?
+    .execute(params![
+        ids::episode_id(path),
+        history.map(|file| file.first_commit_at),
+        history.map(|file| &file.first_author),
+        history.map(|file| file.last_commit_at),
+        history.map(|file| &file.last_author)
+    ])?;
+    Ok(())
+}
+
+/// Series of the episode compiled from `path`, if there is one.
+pub fn episode_series(conn: &Connection, path: &str) -> Result<Option<String>> {
+    conn.prepare_cached("SELECT ep_name FROM ep_data WHERE id = ?1")?
+        .query_row(params![ids::episode_id(path)], |row| row.get(0))
+        .optional()
+}
+
+/// Source file of another episode of `series_name` already labelled `label`.
+pub fn label_owner(conn: &Connection, series_name: &str, label: &str, path: &str) -> Result<Option<String>> {
+    conn.prepare_cached(
+        "SELECT source_files.path FROM source_files JOIN ep_data ON ep_data.id = source_files.ep_id
+         WHERE ep_data.ep_name = ?1 AND ep_data.ep_label = ?2 AND ep_data.id != ?3",
+    )?
+    .query_row(params![series_name, label, ids::episode_id(path)], |row| row.get(0))
+    .optional()
+}
+
+/// Source file of the first episode of a series, in the order compile reads posts.
+pub fn first_source(conn: &Connection, series_name: &str) -> Result<Option<String>> {
+    conn.prepare_cached(
+        "SELECT source_files.path FROM source_files JOIN ep_data ON ep_data.id = source_files.ep_id
+         WHERE ep_data.ep_name = ?1 ORDER BY source_files.path LIMIT 1",
+    )?
+    .query_row(params![series_name], |row| row.get(0))
+    .optional()
+}
+
+/// Drop series that no longer have any episodes.
+pub fn delete_empty_series(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM series_data WHERE id NOT IN (SELECT series_id FROM ep_data WHERE series_id IS NOT NULL)",
+        [],
+    )
+}
+
+/// Drop franchises no series belongs to any more.
+pub fn delete_unused_franchises(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM franchises WHERE id NOT IN (SELECT franchise_id FROM series_data WHERE franchise_id IS NOT NULL)",
+        [],
+    )
+}
+
+/// Drop tags no episode uses any more.
+pub fn delete_unused_tags(conn: &Connection) -> Result<usize> {
+    conn.execute("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM episode_tags)", [])
+}
+
+/// Drop seasons no series or episode falls in any more.
+pub fn delete_unused_seasons(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM seasons WHERE id NOT IN (SELECT season_id FROM series_data WHERE season_id IS NOT NULL)
+         AND id NOT IN (SELECT season_id FROM ep_data WHERE season_id IS NOT NULL)",
+        [],
+    )
+}
+
+/// Every series with the numeric episode numbers of its episodes, ordered by series name.
+pub fn episode_numbers(conn: &Connection) -> Result<Vec<(i64, String, Vec<f64>)>> {
+    let mut stmt = conn.prepare(
+        "SELECT series_data.id, series_data.series_name, ep_data.ep_num
+         FROM series_data LEFT JOIN ep_data ON ep_data.series_id = series_data.id
+         ORDER BY series_data.series_name",
+    )?;
+    let mut rows = stmt.query([])?;
+    let mut series: Vec<(i64, String, Vec<f64>)> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        if series.last().map(|last| last.0) != Some(id) {
+            series.push((id, row.get(1)?, Vec::new()));
+        }
+        if let Some(num) = row.get::<_, Option<f64>>(2)? {
+            series.last_mut().unwrap().2.push(num);
+        }
+    }
+    Ok(series)
+}
+
+pub fn set_coverage(conn: &Connection, series_id: i64, coverage: &Coverage) -> Result<()> {
+    conn.prepare_cached("UPDATE series_data SET expected_episodes = ?2, observed_episodes = ?3 WHERE id = ?1")?
+        .execute(params![series_id, coverage.expected, coverage.observed])?;
+    Ok(())
+}
+
+/// Series names as cleaned from episode titles, before aliases were applied.
+pub fn raw_series_names(conn: &Connection) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT DISTINCT raw_series_name FROM ep_data WHERE raw_series_name IS NOT NULL")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
@@ -0,0 +1,140 @@
+//! Turn one eplot markdown post into the episode row the compiler stores.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::frontmatter::{self, FrontMatterError};
+use crate::normalize::{EplotTitles, NormalizeTitle};
+use crate::sections::{parse_sections, Section};
+use crate::tags;
+
+/// Everything the compiler extracts from a single markdown file.
+#[derive(Debug, Clone)]
+pub struct Episode {
+    /// Canonical series name, also stored as the episode name
+    pub series_name: String,
+    /// Series name cleaned from the title, before aliases are applied
+    pub raw_series_name: String,
+    /// Episode number as written in the file name, e.g. `01` or `12.5`
+    pub ep_label: String,
+    /// Numeric episode number, `None` when the label isn't a number
+    pub ep_num: Option<f64>,
+    pub ep_year: Option<i64>,
+    /// Broadcast month, 1 to 12
+    pub ep_month: Option<i64>,
+    pub abstract_text: String,
+    /// Plot outline parsed out of the abstract
+    pub sections: Vec<Section>,
+    /// Full markdown text after the front matter
+    pub body: String,
+    /// Every front-matter tag, trimmed and deduplicated
+    pub tags: Vec<String>,
+}
+
+/// Numeric value of an episode label such as `01` or a `12.5` special.
+pub fn parse_ep_num(label: &str) -> Option<f64> {
+    label.trim().parse::<f64>().ok().filter(|num| num.is_finite() && *num >= 0.0)
+}
+
+/// Parse the contents of `filename` into an [`Episode`].
+pub fn parse_episode(filename: &str, content: &str) -> Result<Episode, FrontMatterError> {
+    parse_episode_with(filename, content, &EplotTitles)
+}
+
+/// Like [`parse_episode`], deriving the series name with a custom normalizer.
+pub fn parse_episode_with(
+    filename: &str,
+    content: &str,
+    normalizer: &dyn NormalizeTitle,
+) -> Result<Episode, FrontMatterError> {
+    static YYYYMM_RE: OnceLock<Regex> = OnceLock::new();
+    // ASCII only, since `\d` also matches full-width digits like `２０２５０７`
+    let yyyymm_re = YYYYMM_RE.get_or_init(|| Regex::new(r"([0-9]{4})([0-9]{2})").unwrap());
+
+    let doc = frontmatter::parse(content)?;
+    let front_matter = &doc.front_matter;
+    let parts: Vec<&str> = filename.split('_').collect();
+    let (series_name, ep_label) = if parts.len() >= 2 {
+        // Get title from front matter or filename
+        let full_title = front_matter.title.clone().unwrap_or_else(|| parts[0].to_string());
+
+        // Clean the series name by removing episode number if present at end
+        let clean_name = normalizer.series_name(&full_title);
+
+        (clean_name, parts[1].trim_end_matches(".md").to_string())
+    } else {
+        (filename.to_string(), "".to_string())
+    };
+
+    let ep_num = parse_ep_num(&ep_label);
+
+    let mut ep_year = None;
+    let mut ep_month = None;
+
+    if let Some(yyyymm) = front_matter.tags.iter().find_map(|tag| yyyymm_re.captures(tag)) {
+        ep_year = yyyymm[1].parse().ok();
+        ep_month = yyyymm[2].parse().ok().filter(|month| (1..=12).contains(month));
+    }
+
+    let mut abstract_text = front_matter
+        .description
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default()
+        .to_string();
+    if abstract_text.is_empty() {
+        // Fall back to the start of the body
+        let mut below = String::new();
+        for line in doc.body.lines() {
+            below.push_str(line.trim());
+            below.push(' ');
+        }
+        let below = below.trim();
+        let below_chars: String = below.chars().take(200).collect();
+        if below.chars().count() > 200 {
+            abstract_text = format!("{}...", below_chars);
+        } else {
+            abstract_text = below_chars;
+        }
+    }
+
+    let sections = parse_sections(&abstract_text);
+    Ok(Episode {
+        raw_series_name: series_name.clone(),
+        series_name,
+        ep_label,
+        ep_num,
+        ep_year,
+        ep_month,
+        abstract_text,
+        sections,
+        body: doc.body.trim().to_string(),
+        tags: tags::normalize(&front_matter.tags),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(tags: &str) -> String {
+        format!("---\ntitle: 胆大党 第3集\ntags: {}\n---\n正文\n", tags)
+    }
+
+    #[test]
+    fn reads_the_broadcast_month_from_a_date_tag() {
+        let episode = parse_episode("胆大党_03.md", &post(r#"["动作", "202507"]"#)).unwrap();
+        assert_eq!((episode.ep_year, episode.ep_month), (Some(2025), Some(7)));
+        assert_eq!(episode.ep_num, Some(3.0));
+
+        let episode = parse_episode("胆大党_03.md", &post(r#"["202513"]"#)).unwrap();
+        assert_eq!((episode.ep_year, episode.ep_month), (Some(2025), None));
+    }
+
+    #[test]
+    fn ignores_full_width_date_tags() {
+        let episode = parse_episode("胆大党_03.md", &post(r#"["２０２５０７"]"#)).unwrap();
+        assert_eq!((episode.ep_year, episode.ep_month), (None, None));
+    }
+}
@@ -0,0 +1,85 @@
+//! Error type shared by the compile, sync and export steps.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file or directory failed
+    Io { path: PathBuf, source: io::Error },
+    Sqlite(rusqlite::Error),
+    /// Storing the episode compiled from a post failed, e.g. on a constraint
+    Post { path: PathBuf, source: rusqlite::Error },
+    /// A freshly compiled database failed its consistency checks
+    Verify(String),
+    /// The database was written by a newer version of the compiler
+    SchemaTooNew { found: i64, supported: i64 },
+    /// The series override file is malformed
+    Overrides { path: PathBuf, message: String },
+    /// A source archive could not be read
+    Archive { path: PathBuf, message: String },
+    /// A CSV or TSV sheet could not be written or imported
+    Spreadsheet { path: PathBuf, message: String },
+    /// Syncing or reading the git checkout failed
+    Git(String),
+    /// A strict compile found duplicate, missing or outlying episode numbers
+    Coverage(Vec<String>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io { path: path.into(), source }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Sqlite(err) => write!(f, "database error: {}", err),
+            Error::Post { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Verify(message) => write!(f, "compiled database failed verification: {}", message),
+            Error::SchemaTooNew { found, supported } => write!(
+                f,
+                "database schema version {} is newer than this binary supports ({}); upgrade the compiler",
+                found, supported
+            ),
+            Error::Overrides { path, message }
+            | Error::Archive { path, message }
+            | Error::Spreadsheet { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::Git(message) => write!(f, "{}", message),
+            Error::Coverage(issues) => {
+                write!(f, "{} episode numbering problems:", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Sqlite(err) | Error::Post { source: err, .. } => Some(err),
+            Error::Verify(_)
+            | Error::SchemaTooNew { .. }
+            | Error::Overrides { .. }
+            | Error::Archive { .. }
+            | Error::Spreadsheet { .. }
+            | Error::Git(_)
+            | Error::Coverage(_) => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Sqlite(err)
+    }
+}
@@ -0,0 +1,279 @@
+//! Atom and RSS 2.0 feeds of recently added or changed episodes.
+//!
+//! "Recent" comes from the post's git commits when the blog was compiled from a
+//! checkout, and otherwise from `episode_history`, which compile updates whenever a
+//! post's content hash differs from the one it saw before, so rebuilds don't count as changes.
+//! Entries link to `<site_url>/blog/<post>/`, the blog's own page for the post, and
+//! the feeds themselves are expected to be served from `<site_url>/feeds/`.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use crate::error::{Error, Result};
+
+/// What to write and where the links should point.
+#[derive(Debug, Clone)]
+pub struct FeedOptions {
+    /// Root URL of the blog, without a trailing slash; feeds are linked under `<site_url>/feeds/`
+    pub site_url: String,
+    /// Most entries per feed
+    pub limit: usize,
+    /// Also write RSS 2.0 next to each Atom feed
+    pub rss: bool,
+}
+
+struct Entry {
+    source: String,
+    title: String,
+    summary: String,
+    published: i64,
+    updated: i64,
+}
+
+struct Feed {
+    title: String,
+    /// Paths under the output directory
+    atom_path: String,
+    rss_path: String,
+    entries: Vec<Entry>,
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Control characters aren't allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\t' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Days since 1970-01-01 to (year, month, day), after Howard Hinnant's civil_from_days
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn split_time(secs: i64) -> ((i64, i64, i64), (i64, i64, i64)) {
+    let (days, rest) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    (civil_from_days(days), (rest / 3600, rest % 3600 / 60, rest % 60))
+}
+
+/// e.g. `2025-07-01T12:00:00Z`
+fn rfc3339(secs: i64) -> String {
+    let ((year, month, day), (hour, minute, second)) = split_time(secs);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, second)
+}
+
+/// e.g. `Tue, 01 Jul 2025 12:00:00 GMT`
+fn rfc822(secs: i64) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let ((year, month, day), (hour, minute, second)) = split_time(secs);
+    let weekday = DAYS[secs.div_euclid(86_400).rem_euclid(7) as usize];
+    format!(
+        "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+        weekday,
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        hour,
+        minute,
+        second
+    )
+}
+
+fn post_url(site_url: &str, source: &str) -> String {
+    format!("{}/blog/{}/", site_url, source.trim_end_matches(".md"))
+}
+
+fn atom(feed: &Feed, options: &FeedOptions) -> String {
+    let site = &options.site_url;
+    let updated = feed.entries.iter().map(|entry| entry.updated).max().unwrap_or(0);
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    let _ = writeln!(out, "  <title>{}</title>", escape(&feed.title));
+    let _ = writeln!(out, "  <id>{}/feeds/{}</id>", escape(site), feed.atom_path);
+    let _ = writeln!(out, "  <link href=\"{}/\"/>", escape(site));
+    let _ = writeln!(out, "  <link rel=\"self\" href=\"{}/feeds/{}\"/>", escape(site), feed.atom_path);
+    let _ = writeln!(out, "  <updated>{}</updated>", rfc3339(updated));
+    out.push_str("  <author><name>eplot</name></author>\n");
+    for entry in &feed.entries {
+        let url = escape(&post_url(site, &entry.source));
+        out.push_str("  <entry>\n");
+        let _ = writeln!(out, "    <title>{}</title>", escape(&entry.title));
+        let _ = writeln!(out, "    <id>{}</id>", url);
+        let _ = writeln!(out, "    <link href=\"{}\"/>", url);
+        let _ = writeln!(out, "    <published>{}</published>", rfc3339(entry.published));
+        let _ = writeln!(out, "    <updated>{}</updated>", rfc3339(entry.updated));
+        let _ = writeln!(out, "    <summary>{}</summary>", escape(&entry.summary));
+        out.push_str("  </entry>\n");
+    }
+    out.push_str("</feed>\n");
+    out
+}
+
+fn rss(feed: &Feed, options: &FeedOptions) -> String {
+    let site = &options.site_url;
+    let updated = feed.entries.iter().map(|entry| entry.updated).max().unwrap_or(0);
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rss version=\"2.0\">\n<channel>\n");
+    let _ = writeln!(out, "  <title>{}</title>", escape(&feed.title));
+    let _ = writeln!(out, "  <link>{}/</link>", escape(site));
+    let _ = writeln!(out, "  <description>{}</description>", escape(&feed.title));
+    let _ = writeln!(out, "  <lastBuildDate>{}</lastBuildDate>", rfc822(updated));
+    for entry in &feed.entries {
+        let url = escape(&post_url(site, &entry.source));
+        out.push_str("  <item>\n");
+        let _ = writeln!(out, "    <title>{}</title>", escape(&entry.title));
+        let _ = writeln!(out, "    <link>{}</link>", url);
+        let _ = writeln!(out, "    <guid isPermaLink=\"true\">{}</guid>", url);
+        let _ = writeln!(out, "    <pubDate>{}</pubDate>", rfc822(entry.updated));
+        let _ = writeln!(out, "    <description>{}</description>", escape(&entry.summary));
+        out.push_str("  </item>\n");
+    }
+    out.push_str("</channel>\n</rss>\n");
+    out
+}
+
+// Most recently changed episodes, optionally of one series
+fn recent(conn: &Connection, series_id: Option<i64>, limit: usize) -> Result<Vec<Entry>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT source_files.path, COALESCE(series_data.display_name, series_data.series_name), ep_data.ep_label,
+                ep_data.abstract,
+                COALESCE(ep_data.first_commit_at, episode_history.first_seen) AS published,
+                COALESCE(ep_data.last_commit_at, episode_history.last_changed) AS updated
+         FROM ep_data
+         JOIN series_data ON series_data.id = ep_data.series_id
+         JOIN source_files ON source_files.ep_id = ep_data.id
+         JOIN episode_history ON episode_history.ep_id = ep_data.id
+         WHERE ?1 IS NULL OR ep_data.series_id = ?1
+         ORDER BY updated DESC, source_files.path
+         LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(params![series_id, limit as i64], |row| {
+        let name: String = row.get(1)?;
+        let label: String = row.get(2)?;
+        Ok(Entry {
+            source: row.get(0)?,
+            title: format!("{} {}", name, label).trim().to_string(),
+            summary: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            published: row.get(4)?,
+            updated: row.get(5)?,
+        })
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn write(path: &Path, text: &str) -> Result<()> {
+    fs::write(path, text).map_err(|err| Error::io(path, err))
+}
+
+/// Write `atom.xml` and one `series/<id>.atom.xml` per series under `out_dir`,
+/// plus `.rss.xml` twins when asked, and remove other feeds left in `series/` by earlier runs.
+/// Returns the number of feeds written.
+pub fn write_feeds(conn: &Connection, out_dir: &Path, options: &FeedOptions) -> Result<usize> {
+    let series_dir = out_dir.join("series");
+    fs::create_dir_all(&series_dir).map_err(|err| Error::io(&series_dir, err))?;
+
+    let mut feeds = vec![Feed {
+        title: "eplot".to_string(),
+        atom_path: "atom.xml".to_string(),
+        rss_path: "rss.xml".to_string(),
+        entries: recent(conn, None, options.limit)?,
+    }];
+    let mut stmt = conn.prepare("SELECT id, COALESCE(display_name, series_name) FROM series_data ORDER BY series_name")?;
+    let series = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, name) in series {
+        feeds.push(Feed {
+            title: format!("eplot: {}", name),
+            atom_path: format!("series/{}.atom.xml", id),
+            rss_path: format!("series/{}.rss.xml", id),
+            entries: recent(conn, Some(id), options.limit)?,
+        });
+    }
+
+    let mut written = HashSet::new();
+    for feed in &feeds {
+        write(&out_dir.join(&feed.atom_path), &atom(feed, options))?;
+        written.insert(out_dir.join(&feed.atom_path));
+        if options.rss {
+            write(&out_dir.join(&feed.rss_path), &rss(feed, options))?;
+            written.insert(out_dir.join(&feed.rss_path));
+        }
+    }
+    // Series that no longer exist would otherwise keep their feeds
+    let entries = fs::read_dir(&series_dir).map_err(|err| Error::io(&series_dir, err))?;
+    for entry in entries {
+        let path = entry.map_err(|err| Error::io(&series_dir, err))?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if (name.ends_with(".atom.xml") || name.ends_with(".rss.xml")) && !written.contains(&path) {
+            fs::remove_file(&path).map_err(|err| Error::io(&path, err))?;
+        }
+    }
+    Ok(feeds.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::fixtures::titled_post;
+    use crate::compile::{compile, CompileOptions};
+    use crate::ids;
+
+    fn series_feeds(out_dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(out_dir.join("series"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn removes_feeds_of_series_that_are_gone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        let options = FeedOptions {
+            site_url: "https://example.com".to_string(),
+            limit: 10,
+            rss: true,
+        };
+        let posts = [titled_post("a_01.md", "胆大党", "202507"), titled_post("b_01.md", "孤独摇滚！", "202507")];
+        compile(&conn, &posts, &CompileOptions::default()).unwrap();
+        assert_eq!(write_feeds(&conn, tmp.path(), &options).unwrap(), 3);
+        assert_eq!(series_feeds(tmp.path()).len(), 4);
+
+        compile(&conn, &posts[..1], &CompileOptions::default()).unwrap();
+        let atom_only = FeedOptions { rss: false, ..options };
+        write_feeds(&conn, tmp.path(), &atom_only).unwrap();
+        assert_eq!(series_feeds(tmp.path()), [format!("{}.atom.xml", ids::series_id("胆大党"))]);
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(rfc3339(1_751_371_200), "2025-07-01T12:00:00Z");
+        assert_eq!(rfc822(1_751_371_200), "Tue, 01 Jul 2025 12:00:00 GMT");
+    }
+}
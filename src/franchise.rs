@@ -0,0 +1,162 @@
+//! Link the seasons of one show into a franchise.
+//!
+//! Series names keep their season marker (`间谍过家家 第二季`, `无职转生Ⅱ`,
+//! `Season 2`, `2nd Season`), so the franchise is whatever comes before the first
+//! season or part marker, and the ordinal is read from the season marker.
+//! The override file can name the franchise or season explicitly.
+
+use std::sync::OnceLock;
+
+use regex::{Captures, Regex};
+
+use crate::normalize::parse_number;
+use crate::overrides::Overrides;
+
+/// The franchise a series belongs to and which season of it the series is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FranchiseSeason {
+    pub franchise_name: String,
+    /// 1 for series without a season marker
+    pub season: i64,
+}
+
+// Arabic digits in either width, or Chinese numerals
+const NUM: &str = r"[0-9０-９]+|[一二三四五六七八九十两]+";
+const ROMAN: &[char] = &['Ⅰ', 'Ⅱ', 'Ⅲ', 'Ⅳ', 'Ⅴ', 'Ⅵ', 'Ⅶ', 'Ⅷ', 'Ⅸ', 'Ⅹ'];
+const ASCII_ROMAN: &[&str] = &["I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX", "X"];
+
+fn season_markers() -> &'static [Regex] {
+    static MARKERS: OnceLock<Vec<Regex>> = OnceLock::new();
+    MARKERS.get_or_init(|| {
+        [
+            // 第二季, 第3期
+            format!(r"第\s*(?P<n>{NUM})\s*[季期]"),
+            // Season 2
+            format!(r"(?i)\bseason\s*(?P<n>{NUM})\b"),
+            // 2nd Season
+            r"(?i)\b(?P<n>\d+)(?:st|nd|rd|th)\s+season\b".to_string(),
+            // 无职转生Ⅱ
+            r"(?P<r>[ⅠⅡⅢⅣⅤⅥⅦⅧⅨⅩ])".to_string(),
+            // Overlord III; single letters are too likely to be part of the name
+            r"(?:^|\s)(?P<a>II|III|IV|VI|VII|VIII|IX)(?:\s|$)".to_string(),
+        ]
+        .iter()
+        .map(|pattern| Regex::new(pattern).unwrap())
+        .collect()
+    })
+}
+
+// Split cours of one season; they end the franchise name but don't change the ordinal
+fn part_markers() -> &'static Regex {
+    static PART: OnceLock<Regex> = OnceLock::new();
+    PART.get_or_init(|| Regex::new(&format!(r"(?i)第\s*(?:{NUM})\s*(?:部分|部|クール)|\b(?:part|cour)\s*\d+\b")).unwrap())
+}
+
+// Season numbers start at 1; a `Season 0` is left to the default
+fn ordinal(caps: &Captures) -> Option<i64> {
+    if let Some(n) = caps.name("n") {
+        return parse_number(n.as_str()).filter(|n| *n >= 1);
+    }
+    if let Some(r) = caps.name("r") {
+        return ROMAN.iter().position(|&c| r.as_str().starts_with(c)).map(|i| i as i64 + 1);
+    }
+    let a = caps.name("a")?.as_str();
+    ASCII_ROMAN.iter().position(|&roman| roman == a).map(|i| i as i64 + 1)
+}
+
+/// Split a series name into the name shared by every season and the season ordinal.
+pub fn split_season(series_name: &str) -> (&str, i64) {
+    let season = season_markers()
+        .iter()
+        .filter_map(|marker| marker.captures(series_name))
+        .min_by_key(|caps| caps.get(0).unwrap().start());
+    let part = part_markers().find(series_name).map(|found| found.start());
+    let start = season.as_ref().map(|caps| caps.get(0).unwrap().start()).into_iter().chain(part).min();
+    let Some(start) = start else {
+        return (series_name, 1);
+    };
+    let base = series_name[..start].trim_end().trim_end_matches(['-', '–', '—', '|', ':', '：', '·']).trim_end();
+    let season = season.as_ref().and_then(ordinal).unwrap_or(1);
+    // A name that is nothing but a season marker is its own franchise
+    if base.is_empty() {
+        (series_name, season)
+    } else {
+        (base, season)
+    }
+}
+
+/// Franchise and season for a canonical series name.
+///
+/// The shared name goes through the alias map, so `我推的孩子 第二季` lands in the
+/// franchise of `【我推的孩子】` when that alias is listed.
+pub fn franchise_of(series_name: &str, overrides: &Overrides) -> FranchiseSeason {
+    let fixed = overrides.series(series_name);
+    let (base, season) = split_season(series_name);
+    let base = overrides.canonical(base);
+    let franchise_name = fixed
+        .and_then(|fixed| fixed.franchise.as_deref())
+        .or_else(|| overrides.series(base).and_then(|fixed| fixed.franchise.as_deref()))
+        .unwrap_or(base);
+    FranchiseSeason {
+        franchise_name: franchise_name.to_string(),
+        season: fixed.and_then(|fixed| fixed.season).unwrap_or(season),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_season_markers() {
+        let cases = [
+            ("间谍过家家", ("间谍过家家", 1)),
+            ("间谍过家家 第二季", ("间谍过家家", 2)),
+            ("灵能百分百 第3季 路人超能100 Ⅲ", ("灵能百分百", 3)),
+            ("无职转生Ⅱ", ("无职转生", 2)),
+            ("无职转生Ⅱ 第2部分", ("无职转生", 2)),
+            ("Re:Zero Season 2", ("Re:Zero", 2)),
+            ("Overlord 2nd Season", ("Overlord", 2)),
+            ("Overlord III", ("Overlord", 3)),
+            ("咒术回战 第２期", ("咒术回战", 2)),
+            ("咒术回战 第十二季", ("咒术回战", 12)),
+            ("Mushoku Tensei Part 2", ("Mushoku Tensei", 1)),
+            ("Mushoku Tensei - Season 2", ("Mushoku Tensei", 2)),
+            // A single letter is too likely to be part of the name
+            ("Fate Zero V", ("Fate Zero V", 1)),
+            ("第二季", ("第二季", 2)),
+            ("某番 Season 0", ("某番", 1)),
+            ("某番 第0季", ("某番", 1)),
+            ("某番 第０期", ("某番", 1)),
+            ("某番 0th Season", ("某番", 1)),
+        ];
+        for (name, split) in cases {
+            assert_eq!(split_season(name), split, "{}", name);
+        }
+    }
+
+    #[test]
+    fn groups_seasons_through_the_override_file() {
+        let overrides = Overrides::parse(
+            r#"
+"【我推的孩子】":
+  aliases: ["我推的孩子"]
+"胆大党":
+  franchise: "Dandadan"
+"间谍过家家 代号：白":
+  franchise: "间谍过家家"
+  season: 4
+"#,
+        )
+        .unwrap();
+        let franchise = |name| {
+            let FranchiseSeason { franchise_name, season } = franchise_of(name, &overrides);
+            (franchise_name, season)
+        };
+        assert_eq!(franchise("我推的孩子 第二季"), ("【我推的孩子】".to_string(), 2));
+        assert_eq!(franchise("胆大党 第二季"), ("Dandadan".to_string(), 2));
+        assert_eq!(franchise("胆大党"), ("Dandadan".to_string(), 1));
+        assert_eq!(franchise("间谍过家家 代号：白"), ("间谍过家家".to_string(), 4));
+        assert_eq!(franchise("孤独摇滚！"), ("孤独摇滚！".to_string(), 1));
+    }
+}
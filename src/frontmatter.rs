@@ -0,0 +1,186 @@
+//! Parse the YAML front matter at the top of an eplot blog post.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Deserializer};
+use serde_yaml::Value;
+
+/// Typed view of the block between the first two `---` lines of a post.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FrontMatter {
+    #[serde(default, deserialize_with = "opt_scalar")]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "opt_scalar")]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "scalar_list")]
+    pub tags: Vec<String>,
+    #[serde(default, rename = "pubDate", deserialize_with = "opt_scalar")]
+    pub pub_date: Option<String>,
+    #[serde(default, rename = "updatedDate", deserialize_with = "opt_scalar")]
+    pub updated_date: Option<String>,
+    #[serde(default, rename = "heroImage", deserialize_with = "opt_scalar")]
+    pub hero_image: Option<String>,
+    // Any other keys the blog authors add, kept verbatim
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+/// A post split into its front matter and markdown body.
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub front_matter: FrontMatter,
+    pub body: String,
+    /// 1-based line number in the file where the body starts
+    pub body_line: usize,
+}
+
+#[derive(Debug)]
+pub enum FrontMatterError {
+    /// The file does not start with a `---` line
+    Missing,
+    /// No closing `---` line after the opening one
+    Unterminated,
+    /// The block is not valid YAML or has the wrong shape
+    Yaml { line: Option<usize>, message: String },
+}
+
+impl fmt::Display for FrontMatterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrontMatterError::Missing => write!(f, "missing front matter (file must start with `---`)"),
+            FrontMatterError::Unterminated => write!(f, "unterminated front matter (no closing `---`)"),
+            FrontMatterError::Yaml { message, .. } => write!(f, "invalid front matter: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for FrontMatterError {}
+
+/// Split `content` into its raw YAML block and body.
+/// Returns the YAML text, the body text and the 1-based line the body starts on.
+pub fn split(content: &str) -> Result<(&str, &str, usize), FrontMatterError> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut lines = content.split_inclusive('\n');
+    let yaml_start = match lines.next() {
+        Some(first) if first.trim_end() == "---" => first.len(),
+        _ => return Err(FrontMatterError::Missing),
+    };
+    let mut offset = yaml_start;
+    for (idx, line) in lines.enumerate() {
+        let next = offset + line.len();
+        if line.trim_end() == "---" {
+            // idx 0 is line 2 of the file, so the body starts on idx + 3
+            return Ok((&content[yaml_start..offset], &content[next..], idx + 3));
+        }
+        offset = next;
+    }
+    Err(FrontMatterError::Unterminated)
+}
+
+/// 1-based line of the top-level `key:` entry in the front matter, if present.
+pub fn key_line(content: &str, key: &str) -> Option<usize> {
+    let (yaml, _, _) = split(content).ok()?;
+    yaml.lines()
+        .position(|line| line.strip_prefix(key).is_some_and(|rest| rest.trim_start().starts_with(':')))
+        // The YAML block starts on line 2
+        .map(|idx| idx + 2)
+}
+
+/// Parse a whole markdown file into its front matter and body.
+pub fn parse(content: &str) -> Result<Document, FrontMatterError> {
+    let (yaml, body, body_line) = split(content)?;
+    let front_matter = if yaml.trim().is_empty() {
+        FrontMatter::default()
+    } else {
+        // Pad for the opening `---` so YAML error locations match file lines
+        serde_yaml::from_str(&format!("\n{}", yaml)).map_err(|err| FrontMatterError::Yaml {
+            line: err.location().map(|loc| loc.line()),
+            message: err.to_string(),
+        })?
+    };
+    Ok(Document {
+        front_matter,
+        body: body.to_string(),
+        body_line,
+    })
+}
+
+fn scalar_to_string(value: Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+// Titles like `86` or tags like `202507` come through as YAML numbers
+fn opt_scalar<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        value => scalar_to_string(value)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom("expected a string")),
+    }
+}
+
+fn scalar_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(Vec::new()),
+        Value::Sequence(items) => items
+            .into_iter()
+            .map(|item| scalar_to_string(item).ok_or_else(|| serde::de::Error::custom("expected a list of strings")))
+            .collect(),
+        value => scalar_to_string(value)
+            .map(|s| vec![s])
+            .ok_or_else(|| serde::de::Error::custom("expected a list of strings")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_scalars_and_lists() {
+        let content = "---
+title: '86 不存在的战区'
+description: \"It's Lena's war\"
+tags:
+  - 202104
+  - 'spring'
+---
+
+title: not front matter
+";
+        let doc = parse(content).unwrap();
+        let front = &doc.front_matter;
+        assert_eq!(front.title.as_deref(), Some("86 不存在的战区"));
+        assert_eq!(front.description.as_deref(), Some("It's Lena's war"));
+        assert_eq!(front.tags, ["202104", "spring"]);
+        assert_eq!(doc.body, "\ntitle: not front matter\n");
+        assert_eq!(doc.body_line, 8);
+        assert_eq!(key_line(content, "tags"), Some(4));
+        assert_eq!(key_line(content, "title"), Some(2));
+        assert_eq!(key_line(content, "pubDate"), None);
+    }
+
+    #[test]
+    fn splits_off_a_byte_order_mark_and_empty_block() {
+        assert_eq!(split("\u{feff}---\n---\nbody").unwrap(), ("", "body", 3));
+        assert!(parse("---\r\n---\r\n").unwrap().front_matter.title.is_none());
+    }
+
+    #[test]
+    fn reports_malformed_front_matter() {
+        assert!(matches!(parse("title: x\n---\n"), Err(FrontMatterError::Missing)));
+        assert!(matches!(parse("---\ntitle: x\n"), Err(FrontMatterError::Unterminated)));
+        assert_eq!(key_line("---\ntitle: x\n", "title"), None);
+        match parse("---\ntitle: x\ntags: []\ndescription: y: z\n---\n") {
+            Err(FrontMatterError::Yaml { line, .. }) => assert_eq!(line, Some(4)),
+            other => panic!("expected a YAML error, got {:?}", other),
+        }
+        assert!(matches!(parse("---\ntitle: [a, b]\n---\n"), Err(FrontMatterError::Yaml { .. })));
+    }
+}
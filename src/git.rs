@@ -0,0 +1,240 @@
+//! Keep the local eplot checkout in sync with its remote, in-process through libgit2.
+//!
+//! The checkout is managed by the compiler: sync fetches the remote and force-checks
+//! out the requested branch, tag or commit, discarding local edits to tracked files.
+
+use std::path::{Path, PathBuf};
+
+use git2::build::CheckoutBuilder;
+use git2::{AutotagOption, FetchOptions, Oid, Repository};
+
+use crate::error::{Error, Result};
+
+/// Where to sync from and what to check out.
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    /// Remote URL, or the path of a local (bare) repository
+    pub url: String,
+    pub dir: PathBuf,
+    /// Branch, tag or commit to check out; the remote's default branch when `None`
+    pub reference: Option<String>,
+    /// Only fetch this many commits of history; ignored for local repositories
+    pub depth: Option<i32>,
+}
+
+/// What [`sync`] did.
+#[derive(Debug, Clone)]
+pub struct Synced {
+    /// Commit checked out
+    pub commit: String,
+    /// The checkout was created rather than fetched into
+    pub cloned: bool,
+    /// `depth` was dropped because the remote is a local repository
+    pub depth_ignored: bool,
+}
+
+fn git_error(context: &str, err: git2::Error) -> Error {
+    Error::Git(format!("{}: {}", context, err.message()))
+}
+
+/// Commit currently checked out in `dir`, if it is a git checkout.
+pub fn head_commit(dir: &Path) -> Option<String> {
+    let repo = Repository::open(dir).ok()?;
+    let commit = repo.head().ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+// The checkout, created empty with an `origin` remote when it doesn't exist yet,
+// and whether it was created
+fn open_or_init(options: &SyncOptions) -> Result<(Repository, bool)> {
+    let cloned = !options.dir.join(".git").exists();
+    let repo = if !cloned {
+        Repository::open(&options.dir).map_err(|err| git_error("failed to open checkout", err))?
+    } else {
+        Repository::init(&options.dir).map_err(|err| git_error("failed to create checkout", err))?
+    };
+    match repo.find_remote("origin") {
+        Ok(remote) if remote.url() == Some(options.url.as_str()) => {}
+        Ok(_) => repo
+            .remote_set_url("origin", &options.url)
+            .map_err(|err| git_error("failed to update origin", err))?,
+        Err(_) => {
+            repo.remote("origin", &options.url)
+                .map_err(|err| git_error("failed to add origin", err))?;
+        }
+    }
+    Ok((repo, cloned))
+}
+
+fn fetch_options(depth: Option<i32>) -> FetchOptions<'static> {
+    let mut fetch = FetchOptions::new();
+    fetch.download_tags(AutotagOption::All);
+    if let Some(depth) = depth {
+        fetch.depth(depth);
+    }
+    fetch
+}
+
+// Branch name to check out and the commit it resolves to
+fn resolve(repo: &Repository, reference: &str) -> Option<(Option<String>, Oid)> {
+    let peel = |name: &str| repo.revparse_single(name).ok()?.peel_to_commit().ok().map(|commit| commit.id());
+    if let Some(oid) = peel(&format!("refs/remotes/origin/{}", reference)) {
+        return Some((Some(reference.to_string()), oid));
+    }
+    peel(&format!("refs/tags/{}", reference))
+        .or_else(|| peel(reference))
+        .map(|oid| (None, oid))
+}
+
+/// Fetch `options.url` into `options.dir` and check out the requested commit.
+pub fn sync(options: &SyncOptions) -> Result<Synced> {
+    let (repo, cloned) = open_or_init(options)?;
+    let mut remote = repo.find_remote("origin").map_err(|err| git_error("no origin remote", err))?;
+    // libgit2 only fetches shallow over the network, so local repositories come in full
+    let local = options.url.starts_with("file://") || Path::new(&options.url).exists();
+    let depth = options.depth.filter(|_| !local);
+    let refspecs = ["+refs/heads/*:refs/remotes/origin/*"];
+    remote
+        .fetch(&refspecs, Some(&mut fetch_options(depth)), None)
+        .map_err(|err| git_error("fetch failed", err))?;
+
+    let reference = match &options.reference {
+        Some(reference) => reference.clone(),
+        None => {
+            let head = remote
+                .default_branch()
+                .map_err(|err| git_error("remote has no default branch", err))?;
+            let head = head.as_str().unwrap_or_default();
+            head.strip_prefix("refs/heads/").unwrap_or(head).to_string()
+        }
+    };
+    let mut resolved = resolve(&repo, &reference);
+    // A commit that no branch tip or tag points at, e.g. an older one in a shallow fetch
+    if resolved.is_none() && Oid::from_str(&reference).is_ok() {
+        remote
+            .fetch(&[reference.as_str()], Some(&mut fetch_options(depth)), None)
+            .map_err(|err| git_error(&format!("failed to fetch {}", reference), err))?;
+        resolved = resolve(&repo, &reference);
+    }
+    let Some((branch, oid)) = resolved else {
+        return Err(Error::Git(format!("{} is not a branch, tag or commit of {}", reference, options.url)));
+    };
+
+    let commit = repo.find_commit(oid).map_err(|err| git_error("failed to read commit", err))?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))
+        .map_err(|err| git_error("checkout failed", err))?;
+    match branch {
+        Some(branch) => {
+            // Moves the ref directly; `Repository::branch` refuses to move the branch HEAD is on
+            let name = format!("refs/heads/{}", branch);
+            repo.reference(&name, oid, true, "sync")
+                .map_err(|err| git_error("failed to update branch", err))?;
+            repo.set_head(&name)
+        }
+        None => repo.set_head_detached(oid),
+    }
+    .map_err(|err| git_error("failed to move HEAD", err))?;
+    Ok(Synced {
+        commit: oid.to_string(),
+        cloned,
+        depth_ignored: depth != options.depth,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use std::path::Path;
+
+    use git2::{Index, IndexEntry, IndexTime, Oid, Repository, RepositoryInitOptions, Signature, Time};
+
+    /// An empty bare repository whose HEAD is `main`.
+    pub fn bare_repo(path: &Path) -> Repository {
+        Repository::init_opts(path, RepositoryInitOptions::new().bare(true).initial_head("main")).unwrap()
+    }
+
+    /// Commit `files` (path, content) on top of `main` as `author` at unix second `time`.
+    pub fn commit(repo: &Repository, files: &[(&str, &str)], author: &str, time: i64) -> Oid {
+        let parent = repo.refname_to_id("refs/heads/main").ok().map(|oid| repo.find_commit(oid).unwrap());
+        let mut index = Index::new().unwrap();
+        if let Some(parent) = &parent {
+            index.read_tree(&parent.tree().unwrap()).unwrap();
+        }
+        for (path, content) in files {
+            index
+                .add(&IndexEntry {
+                    ctime: IndexTime::new(0, 0),
+                    mtime: IndexTime::new(0, 0),
+                    dev: 0,
+                    ino: 0,
+                    mode: 0o100644,
+                    uid: 0,
+                    gid: 0,
+                    file_size: content.len() as u32,
+                    id: repo.blob(content.as_bytes()).unwrap(),
+                    flags: 0,
+                    flags_extended: 0,
+                    path: path.as_bytes().to_vec(),
+                })
+                .unwrap();
+        }
+        let tree = repo.find_tree(index.write_tree_to(repo).unwrap()).unwrap();
+        let email = format!("{}@example.com", author.to_lowercase());
+        let sig = Signature::new(author, &email, &Time::new(time, 0)).unwrap();
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("refs/heads/main"), &sig, &sig, "update", &tree, &parents).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{bare_repo, commit};
+    use super::*;
+
+    fn checked_out(dir: &Path) -> String {
+        std::fs::read_to_string(dir.join("file.md")).unwrap()
+    }
+
+    #[test]
+    fn syncs_branches_tags_and_commits_from_a_local_bare_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        let remote = bare_repo(&tmp.path().join("remote.git"));
+        let first = commit(&remote, &[("file.md", "one")], "Alice", 1_700_000_000);
+        remote.tag_lightweight("v1", &remote.find_object(first, None).unwrap(), false).unwrap();
+
+        let mut options = SyncOptions {
+            url: tmp.path().join("remote.git").to_string_lossy().into_owned(),
+            dir: tmp.path().join("checkout"),
+            reference: None,
+            depth: Some(1),
+        };
+        let synced = sync(&options).unwrap();
+        assert_eq!(synced.commit, first.to_string());
+        assert!(synced.cloned && synced.depth_ignored);
+        assert_eq!(checked_out(&options.dir), "one");
+
+        // Syncing again moves the branch HEAD is on
+        let second = commit(&remote, &[("file.md", "two")], "Alice", 1_700_000_100);
+        std::fs::write(options.dir.join("file.md"), "local edit").unwrap();
+        let synced = sync(&options).unwrap();
+        assert_eq!(synced.commit, second.to_string());
+        assert!(!synced.cloned);
+        assert_eq!(checked_out(&options.dir), "two");
+        assert_eq!(head_commit(&options.dir), Some(second.to_string()));
+
+        options.reference = Some("v1".to_string());
+        assert_eq!(sync(&options).unwrap().commit, first.to_string());
+        assert_eq!(checked_out(&options.dir), "one");
+        assert!(Repository::open(&options.dir).unwrap().head_detached().unwrap());
+
+        options.reference = Some(second.to_string());
+        assert_eq!(sync(&options).unwrap().commit, second.to_string());
+        assert_eq!(checked_out(&options.dir), "two");
+
+        options.reference = Some("main".to_string());
+        assert_eq!(sync(&options).unwrap().commit, second.to_string());
+        assert!(!Repository::open(&options.dir).unwrap().head_detached().unwrap());
+
+        options.reference = Some("nope".to_string());
+        assert!(matches!(sync(&options), Err(Error::Git(_))));
+    }
+}
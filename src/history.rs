@@ -0,0 +1,116 @@
+//! When each post was first committed and last changed, and by whom, from the eplot git history.
+//!
+//! Merge commits are skipped so changes are credited to the commits that made them.
+//! In a shallow checkout the oldest fetched commit counts as the first one.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use git2::{DiffOptions, Repository, Sort};
+
+use crate::error::{Error, Result};
+
+/// First and last commit touching one post.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHistory {
+    /// Unix seconds of the commit that added the file
+    pub first_commit_at: i64,
+    pub first_author: String,
+    /// Unix seconds of the latest commit that changed the file
+    pub last_commit_at: i64,
+    pub last_author: String,
+}
+
+fn git_error(err: git2::Error) -> Error {
+    Error::Git(format!("failed to read history: {}", err.message()))
+}
+
+/// History of every post directly inside `blog_subdir`, keyed by file name.
+pub fn file_history(repo_dir: &Path, blog_subdir: &Path) -> Result<HashMap<String, FileHistory>> {
+    let repo = Repository::open(repo_dir).map_err(git_error)?;
+    let mut walk = repo.revwalk().map_err(git_error)?;
+    // Parents before children, so same-second or clock-skewed commits still come in order
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE).map_err(git_error)?;
+    walk.push_head().map_err(git_error)?;
+
+    let mut history: HashMap<String, FileHistory> = HashMap::new();
+    for oid in walk {
+        let commit = repo.find_commit(oid.map_err(git_error)?).map_err(git_error)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let tree = commit.tree().map_err(git_error)?;
+        let parent = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().map_err(git_error)?),
+            Err(_) => None,
+        };
+        let mut diff_options = DiffOptions::new();
+        diff_options.pathspec(blog_subdir);
+        let diff = repo
+            .diff_tree_to_tree(parent.as_ref(), Some(&tree), Some(&mut diff_options))
+            .map_err(git_error)?;
+
+        let time = commit.author().when().seconds();
+        let author = String::from_utf8_lossy(commit.author().name_bytes()).into_owned();
+        for delta in diff.deltas() {
+            let Some(path) = delta.new_file().path().or_else(|| delta.old_file().path()) else {
+                continue;
+            };
+            let Some(name) = path.strip_prefix(blog_subdir).ok().filter(|name| name.components().count() == 1) else {
+                continue;
+            };
+            let name = name.to_string_lossy().into_owned();
+            let file = history.entry(name).or_insert_with(|| FileHistory {
+                first_commit_at: time,
+                first_author: author.clone(),
+                last_commit_at: time,
+                last_author: author.clone(),
+            });
+            file.last_commit_at = time;
+            file.last_author = author.clone();
+        }
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::fixtures::{bare_repo, commit};
+
+    fn history(
+        name: &str,
+        first_commit_at: i64,
+        first_author: &str,
+        last_commit_at: i64,
+        last_author: &str,
+    ) -> (String, FileHistory) {
+        let history = FileHistory {
+            first_commit_at,
+            first_author: first_author.to_string(),
+            last_commit_at,
+            last_author: last_author.to_string(),
+        };
+        (name.to_string(), history)
+    }
+
+    #[test]
+    fn credits_the_first_and_last_commit_of_each_post() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = bare_repo(tmp.path());
+        commit(&repo, &[("_posts/a.md", "a"), ("README.md", "readme")], "Alice", 1_700_000_000);
+        // An edit in the same second as the commit that added the file
+        commit(&repo, &[("_posts/a.md", "a, edited")], "Bob", 1_700_000_000);
+        commit(&repo, &[("_posts/b.md", "b"), ("_posts/drafts/c.md", "c")], "Carol", 1_700_000_100);
+        commit(&repo, &[("_posts/b.md", "b, edited")], "Dave", 1_700_000_200);
+        // An author clock running behind the parent commit
+        commit(&repo, &[("_posts/b.md", "b, edited again")], "Erin", 1_600_000_000);
+        commit(&repo, &[("README.md", "readme, edited")], "Frank", 1_700_000_300);
+        let found = file_history(tmp.path(), Path::new("_posts")).unwrap();
+        let expected = HashMap::from([
+            history("a.md", 1_700_000_000, "Alice", 1_700_000_000, "Bob"),
+            history("b.md", 1_700_000_100, "Carol", 1_600_000_000, "Erin"),
+        ]);
+        assert_eq!(found, expected);
+    }
+}
@@ -0,0 +1,48 @@
+//! Deterministic primary keys, so ids survive full rebuilds.
+//!
+//! Ids are the first bytes of a SHA-256 digest truncated to 53 bits, which keeps
+//! them positive in SQLite and exact as JavaScript numbers on the front end.
+
+use sha2::{Digest, Sha256};
+
+/// Recorded in `build_info` so databases using older ids get rebuilt once.
+pub const ID_SCHEME: &str = "sha256-53";
+
+fn hash_id(namespace: &str, key: &str) -> i64 {
+    let digest = Sha256::new()
+        .chain_update(namespace.as_bytes())
+        .chain_update([0])
+        .chain_update(key.as_bytes())
+        .finalize();
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(bytes) >> 11) as i64
+}
+
+/// Id of the series with this (cleaned) name.
+pub fn series_id(series_name: &str) -> i64 {
+    hash_id("series", series_name)
+}
+
+/// Id of the franchise with this name.
+pub fn franchise_id(franchise_name: &str) -> i64 {
+    hash_id("franchise", franchise_name)
+}
+
+/// Id of the episode compiled from this source file name.
+pub fn episode_id(source_path: &str) -> i64 {
+    hash_id("episode", source_path)
+}
+
+/// Id of the front-matter tag with this name.
+pub fn tag_id(tag: &str) -> i64 {
+    hash_id("tag", tag)
+}
+
+/// Hex-encoded SHA-256 of some text, used to spot changed source files.
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
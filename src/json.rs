@@ -0,0 +1,297 @@
+//! JSON export of the catalog for static sites.
+//!
+//! `export json <dir>` writes three kinds of file, each with a top-level `version`
+//! that is bumped whenever a field is removed or changes meaning (added fields don't
+//! bump it). Ids are the same 53-bit integers as in the database.
+//!
+//! `index.json`, every series ordered by name:
+//! ```text
+//! { "version": 1, "series": [Series] }
+//! Series = { "id", "name", "display_name"?, "year"?, "month"?, "season_id"?,
+//!            "franchise": { "id", "name", "season" },
+//!            "episode_count", "expected_episodes", "observed_episodes",
+//!            "path": "series/<id>.json" }
+//! ```
+//!
+//! `series/<id>.json`, one series and its episodes ordered by number:
+//! ```text
+//! { "version": 1, "series": Series, "episodes": [Episode] }
+//! Episode = { "id", "source", "label", "num"?, "year"?, "month"?, "season_id"?,
+//!             "abstract", "sections": [{ "section_title"?, "subsection_title"?, "body" }],
+//!             "tags": [string], "html" }
+//! ```
+//!
+//! `seasons.json`, every broadcast season with the series that premiered in it:
+//! ```text
+//! { "version": 1, "seasons": [{ "id", "year", "quarter", "name_en", "name_zh", "series": [id] }] }
+//! ```
+//!
+//! Fields marked `?` are `null` when unknown.
+//!
+//! The catalog is read from the compiled database (see [`Catalog::load`]), so run
+//! compile first; imported spreadsheet corrections show up here too.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::body;
+use crate::catalog::{Catalog, CatalogEpisode, CatalogSeries};
+use crate::error::{Error, Result};
+use crate::ids;
+use crate::season::Season;
+
+/// Shape version written into every file.
+pub const JSON_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Franchise<'a> {
+    id: i64,
+    name: &'a str,
+    season: i64,
+}
+
+#[derive(Serialize)]
+struct Series<'a> {
+    id: i64,
+    name: &'a str,
+    display_name: Option<&'a str>,
+    year: Option<i64>,
+    month: Option<i64>,
+    season_id: Option<i64>,
+    franchise: Franchise<'a>,
+    episode_count: usize,
+    expected_episodes: i64,
+    observed_episodes: i64,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct Section<'a> {
+    section_title: Option<&'a str>,
+    subsection_title: Option<&'a str>,
+    body: &'a str,
+}
+
+#[derive(Serialize)]
+struct Episode<'a> {
+    id: i64,
+    source: &'a str,
+    label: &'a str,
+    num: Option<f64>,
+    year: Option<i64>,
+    month: Option<i64>,
+    season_id: Option<i64>,
+    #[serde(rename = "abstract")]
+    abstract_text: &'a str,
+    sections: Vec<Section<'a>>,
+    tags: &'a [String],
+    html: String,
+}
+
+#[derive(Serialize)]
+struct SeasonEntry {
+    id: i64,
+    year: i64,
+    quarter: i64,
+    name_en: String,
+    name_zh: String,
+    series: Vec<i64>,
+}
+
+#[derive(Serialize)]
+struct IndexFile<'a> {
+    version: u32,
+    series: Vec<Series<'a>>,
+}
+
+#[derive(Serialize)]
+struct SeriesFile<'a> {
+    version: u32,
+    series: Series<'a>,
+    episodes: Vec<Episode<'a>>,
+}
+
+#[derive(Serialize)]
+struct SeasonsFile {
+    version: u32,
+    seasons: Vec<SeasonEntry>,
+}
+
+fn series(series: &CatalogSeries) -> Series<'_> {
+    let row = &series.row;
+    Series {
+        id: row.id,
+        name: &row.series_name,
+        display_name: row.display_name.as_deref(),
+        year: row.year,
+        month: row.month,
+        season_id: row.season.map(|season| season.id()),
+        franchise: Franchise {
+            id: ids::franchise_id(&row.franchise.franchise_name),
+            name: &row.franchise.franchise_name,
+            season: row.franchise.season,
+        },
+        episode_count: series.episodes.len(),
+        expected_episodes: series.coverage.expected,
+        observed_episodes: series.coverage.observed,
+        path: format!("series/{}.json", row.id),
+    }
+}
+
+fn episode(compiled: &CatalogEpisode) -> Episode<'_> {
+    let episode = &compiled.episode;
+    Episode {
+        id: compiled.id,
+        source: &compiled.source,
+        label: &episode.ep_label,
+        num: episode.ep_num,
+        year: episode.ep_year,
+        month: episode.ep_month,
+        season_id: Season::from_year_month(episode.ep_year, episode.ep_month).map(|season| season.id()),
+        abstract_text: &episode.abstract_text,
+        sections: episode
+            .sections
+            .iter()
+            .map(|section| Section {
+                section_title: section.section_title.as_deref(),
+                subsection_title: section.subsection_title.as_deref(),
+                body: &section.body,
+            })
+            .collect(),
+        tags: &episode.tags,
+        html: body::render_html(&episode.body),
+    }
+}
+
+fn write_file(path: &Path, value: &impl Serialize) -> Result<()> {
+    let json = serde_json::to_string_pretty(value).expect("catalog serializes");
+    fs::write(path, json + "\n").map_err(|err| Error::io(path, err))
+}
+
+/// Write the catalog under `out_dir`, replacing an earlier export there.
+/// Returns the number of series files written.
+pub fn write_catalog(catalog: &Catalog, out_dir: &Path) -> Result<usize> {
+    let series_dir = out_dir.join("series");
+    fs::create_dir_all(&series_dir).map_err(|err| Error::io(&series_dir, err))?;
+
+    let mut written = HashSet::new();
+    for compiled in &catalog.series {
+        let file = SeriesFile {
+            version: JSON_VERSION,
+            series: series(compiled),
+            episodes: compiled.episodes.iter().map(episode).collect(),
+        };
+        let name = format!("{}.json", compiled.row.id);
+        write_file(&series_dir.join(&name), &file)?;
+        written.insert(name);
+    }
+    // Series that no longer exist would otherwise linger in the export
+    let entries = fs::read_dir(&series_dir).map_err(|err| Error::io(&series_dir, err))?;
+    for entry in entries {
+        let path = entry.map_err(|err| Error::io(&series_dir, err))?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if name.ends_with(".json") && !written.contains(&name) {
+            fs::remove_file(&path).map_err(|err| Error::io(&path, err))?;
+        }
+    }
+
+    let index = IndexFile {
+        version: JSON_VERSION,
+        series: catalog.series.iter().map(series).collect(),
+    };
+    write_file(&out_dir.join("index.json"), &index)?;
+
+    let mut seasons: BTreeMap<Season, Vec<i64>> = BTreeMap::new();
+    for compiled in &catalog.series {
+        if let Some(season) = compiled.row.season {
+            seasons.entry(season).or_default().push(compiled.row.id);
+        }
+    }
+    let seasons = SeasonsFile {
+        version: JSON_VERSION,
+        seasons: seasons
+            .into_iter()
+            .map(|(season, series)| SeasonEntry {
+                id: season.id(),
+                year: season.year,
+                quarter: season.quarter,
+                name_en: season.name_en(),
+                name_zh: season.name_zh(),
+                series,
+            })
+            .collect(),
+    };
+    write_file(&out_dir.join("seasons.json"), &seasons)?;
+    Ok(catalog.series.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::compile::{compile, CompileOptions};
+    use crate::source::fixtures::{post, post_with, titled_post};
+
+    fn read(path: &Path) -> Value {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn exports_what_compile_stored() {
+        let conn = Connection::open_in_memory().unwrap();
+        let posts = [
+            post("胆大党_01.md", "202410"),
+            post("胆大党_02.md", "202410"),
+            titled_post("spy_02.md", "间谍过家家", "202210"),
+            // Same label as spy_02.md, so compile skips it
+            titled_post("spyb_02.md", "间谍过家家", "202210"),
+            post_with("broken.md", "---\ntitle: [\n---\n"),
+        ];
+        compile(&conn, &posts, &CompileOptions::default()).unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let written = write_catalog(&Catalog::load(&conn).unwrap(), out.path()).unwrap();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT series_data.id, series_name, COUNT(ep_data.id) FROM series_data
+                 JOIN ep_data ON ep_data.series_id = series_data.id GROUP BY series_data.id ORDER BY series_name",
+            )
+            .unwrap();
+        let compiled: Vec<(i64, String, i64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(written, compiled.len());
+        let index = read(&out.path().join("index.json"));
+        let exported: Vec<(i64, String, i64)> = index["series"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|series| {
+                let id = series["id"].as_i64().unwrap();
+                let name = series["name"].as_str().unwrap().to_string();
+                (id, name, series["episode_count"].as_i64().unwrap())
+            })
+            .collect();
+        assert_eq!(exported, compiled);
+
+        let mut stmt = conn.prepare("SELECT path FROM source_files ORDER BY path").unwrap();
+        let compiled: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap().map(|row| row.unwrap()).collect();
+        let mut sources = Vec::new();
+        for (id, _, _) in &exported {
+            let file = read(&out.path().join(format!("series/{}.json", id)));
+            for episode in file["episodes"].as_array().unwrap() {
+                sources.push(episode["source"].as_str().unwrap().to_string());
+            }
+        }
+        sources.sort();
+        assert_eq!(sources, compiled);
+        assert!(!sources.iter().any(|source| source == "spyb_02.md"));
+    }
+}
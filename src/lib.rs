@@ -0,0 +1,27 @@
+//! Library side of the eplot data compiler: parsing and database helpers used by the binary.
+
+pub mod body;
+pub mod catalog;
+pub mod compile;
+pub mod coverage;
+pub mod db;
+pub mod episode;
+pub mod error;
+pub mod feed;
+pub mod franchise;
+pub mod frontmatter;
+pub mod git;
+pub mod history;
+pub mod ids;
+pub mod json;
+pub mod lint;
+pub mod migrations;
+pub mod normalize;
+pub mod overrides;
+pub mod season;
+pub mod sections;
+pub mod source;
+pub mod spreadsheet;
+pub mod tags;
+
+pub use error::{Error, Result};
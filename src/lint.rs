@@ -0,0 +1,312 @@
+//! Data-quality checks over the blog posts, reported by the `check` command.
+//!
+//! The compiler skips files it can't parse and quietly stores empty numbers and
+//! dates for the rest; lint reports each of those with the file and line to fix.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::coverage::{Coverage, Issue};
+use crate::episode::{parse_episode, Episode};
+use crate::frontmatter::{self, FrontMatterError};
+use crate::normalize::{parse_number, EplotTitles, NormalizeTitle};
+use crate::overrides::Overrides;
+use crate::source::Post;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The file is skipped or its episode stored incorrectly
+    Error,
+    /// Suspicious; compile warns about it and only fails with `--strict`
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    pub path: PathBuf,
+    /// 1-based line, when the problem is in a specific place in the file
+    pub line: Option<usize>,
+    pub severity: Severity,
+    /// Stable kebab-case identifier, e.g. `missing-date`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.line {
+            Some(line) => write!(f, "{}:{}: ", self.path.display(), line)?,
+            None => write!(f, "{}: ", self.path.display())?,
+        }
+        write!(f, "{}[{}]: {}", severity, self.code, self.message)
+    }
+}
+
+/// Everything `check` found, as written by `check --format json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// Number of markdown files looked at
+    pub files: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub problems: Vec<Problem>,
+}
+
+// A parsed post, kept for the checks that compare posts of one series
+struct Parsed<'a> {
+    post: &'a Post,
+    episode: Episode,
+    title_line: Option<usize>,
+}
+
+/// Check every post, grouping series through `overrides` the way compile does.
+pub fn lint(posts: &[Post], overrides: &Overrides) -> Report {
+    let mut problems = Vec::new();
+    let mut parsed = Vec::new();
+    for post in posts {
+        let mut problem = |line, severity, code, message| {
+            problems.push(Problem {
+                path: post.path.clone(),
+                line,
+                severity,
+                code,
+                message,
+            })
+        };
+        let content = match &post.content {
+            Ok(content) => content,
+            Err(err) => {
+                problem(None, Severity::Error, "unreadable", err.clone());
+                continue;
+            }
+        };
+        let mut episode = match parse_episode(&post.name, content) {
+            Ok(episode) => episode,
+            Err(err) => {
+                let line = match err {
+                    FrontMatterError::Yaml { line, .. } => line,
+                    _ => Some(1),
+                };
+                problem(line, Severity::Error, "front-matter", err.to_string());
+                continue;
+            }
+        };
+        episode.series_name = overrides.canonical(&episode.raw_series_name).to_string();
+        let title_line = frontmatter::key_line(content, "title");
+        let tags_line = frontmatter::key_line(content, "tags");
+
+        if !post.name.contains('_') {
+            problem(
+                None,
+                Severity::Error,
+                "no-episode-number",
+                "file name has no `_<episode>` part, so the episode has no number".to_string(),
+            );
+        } else if episode.ep_num.is_none() {
+            problem(
+                None,
+                Severity::Warning,
+                "non-numeric-episode",
+                format!("episode label `{}` in the file name is not a number", episode.ep_label),
+            );
+        }
+
+        match episode.tags.iter().find(|tag| yyyymm_re().is_match(tag)) {
+            None => problem(
+                tags_line.or(Some(1)),
+                Severity::Error,
+                "missing-date",
+                "no `YYYYMM` broadcast month tag".to_string(),
+            ),
+            Some(tag) if episode.ep_month.is_none() => problem(
+                tags_line,
+                Severity::Error,
+                "invalid-date",
+                format!("tag `{}` does not hold a month between 01 and 12", tag),
+            ),
+            Some(_) => {}
+        }
+
+        if let (Some(title), Some(ep_num)) = (title_episode(content), episode.ep_num) {
+            if title != ep_num {
+                problem(
+                    title_line,
+                    Severity::Warning,
+                    "title-mismatch",
+                    format!("title says episode {} but the file name says {}", title, episode.ep_label),
+                );
+            }
+        }
+
+        parsed.push(Parsed {
+            post,
+            episode,
+            title_line,
+        });
+    }
+    check_numbering(&parsed, &mut problems);
+
+    problems.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    let errors = problems.iter().filter(|problem| problem.severity == Severity::Error).count();
+    Report {
+        files: posts.len(),
+        errors,
+        warnings: problems.len() - errors,
+        problems,
+    }
+}
+
+fn yyyymm_re() -> &'static Regex {
+    static YYYYMM_RE: OnceLock<Regex> = OnceLock::new();
+    // ASCII digits only, like compile
+    YYYYMM_RE.get_or_init(|| Regex::new(r"[0-9]{6}").unwrap())
+}
+
+// Episode number in the post title, from the marker the series name was cleaned of
+fn title_episode(content: &str) -> Option<f64> {
+    static NUMBER: OnceLock<Regex> = OnceLock::new();
+    let number = NUMBER
+        .get_or_init(|| Regex::new(r"[0-9０-９]+(?:[.．][0-9０-９]+)?|[一二三四五六七八九十百零〇两]+").unwrap());
+    let title = frontmatter::parse(content).ok()?.front_matter.title?;
+    let title = title.trim();
+    let series = EplotTitles.series_name(title);
+    let marker = title.strip_prefix(series.as_str())?;
+    let found = number.find(marker)?.as_str();
+    let ascii: String = found
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap(),
+            '．' => '.',
+            _ => c,
+        })
+        .collect();
+    ascii.parse().ok().or_else(|| parse_number(found).map(|num| num as f64))
+}
+
+// Duplicate episode numbers, gaps and outliers, per series
+fn check_numbering(parsed: &[Parsed], problems: &mut Vec<Problem>) {
+    let mut series: BTreeMap<&str, Vec<&Parsed>> = BTreeMap::new();
+    for item in parsed {
+        series.entry(&item.episode.series_name).or_default().push(item);
+    }
+    for (name, mut episodes) in series {
+        // Compile keeps the first post of a label by file name and skips the rest
+        episodes.sort_by(|a, b| a.episode.ep_label.cmp(&b.episode.ep_label).then(a.post.name.cmp(&b.post.name)));
+        for pair in episodes.windows(2) {
+            let (prev, next) = (pair[0], pair[1]);
+            if prev.episode.ep_label == next.episode.ep_label {
+                problems.push(Problem {
+                    path: next.post.path.clone(),
+                    line: next.title_line,
+                    severity: Severity::Warning,
+                    code: "duplicate-episode",
+                    message: format!(
+                        "{} episode {} is also in {}, so compile skips this post",
+                        name,
+                        next.episode.ep_label,
+                        prev.post.path.display()
+                    ),
+                });
+            }
+        }
+        episodes.dedup_by(|next, prev| next.episode.ep_label == prev.episode.ep_label);
+        episodes.retain(|item| item.episode.ep_num.is_some());
+        episodes.sort_by(|a, b| a.episode.ep_num.partial_cmp(&b.episode.ep_num).unwrap().then(a.post.path.cmp(&b.post.path)));
+        for pair in episodes.windows(2) {
+            let (prev, next) = (pair[0], pair[1]);
+            if prev.episode.ep_num == next.episode.ep_num {
+                problems.push(Problem {
+                    path: next.post.path.clone(),
+                    line: next.title_line,
+                    severity: Severity::Warning,
+                    code: "duplicate-episode",
+                    message: format!(
+                        "{} episode {} is also compiled from {}",
+                        name,
+                        next.episode.ep_label,
+                        prev.post.path.display()
+                    ),
+                });
+            }
+        }
+        let nums: Vec<f64> = episodes.iter().map(|item| item.episode.ep_num.unwrap()).collect();
+        let coverage = Coverage::of(&nums);
+        let post_of = |num: f64| episodes.iter().find(|item| item.episode.ep_num == Some(num));
+        for &(from, to) in &coverage.gaps {
+            let next = post_of(to as f64 + 1.0).unwrap();
+            problems.push(Problem {
+                path: next.post.path.clone(),
+                line: None,
+                severity: Severity::Warning,
+                code: "episode-gap",
+                message: format!("{} {} before episode {}", name, Issue::Gap(from, to), next.episode.ep_label),
+            });
+        }
+        for &num in &coverage.outliers {
+            let outlier = post_of(num).unwrap();
+            problems.push(Problem {
+                path: outlier.post.path.clone(),
+                line: None,
+                severity: Severity::Warning,
+                code: "episode-outlier",
+                message: format!("{} {}", name, Issue::Outlier(num)),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::fixtures::post_with;
+
+    fn codes(report: &Report) -> Vec<&'static str> {
+        report.problems.iter().map(|problem| problem.code).collect()
+    }
+
+    #[test]
+    fn reports_full_width_date_tags_as_missing() {
+        let posts = [post_with("胆大党_01.md", "---\ntitle: 胆大党 1\ntags: [\"２０２５０７\"]\n---\n正文\n")];
+        let report = lint(&posts, &Overrides::default());
+        assert_eq!(codes(&report), ["missing-date"]);
+        assert_eq!(report.problems[0].line, Some(3));
+    }
+
+    #[test]
+    fn reports_duplicates_as_warnings_like_compile() {
+        let content = "---\ntitle: 胆大党\ntags: [\"202507\"]\n---\n正文\n";
+        let posts = [post_with("a_01.md", content), post_with("a_1.md", content), post_with("b_01.md", content)];
+        let report = lint(&posts, &Overrides::default());
+        let found: Vec<(&str, &str)> = report
+            .problems
+            .iter()
+            .map(|problem| (problem.path.to_str().unwrap(), problem.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("a_1.md", "胆大党 episode 1 is also compiled from a_01.md"),
+                ("b_01.md", "胆大党 episode 01 is also in a_01.md, so compile skips this post"),
+            ]
+        );
+        assert_eq!((report.errors, report.warnings), (0, 2));
+    }
+
+    #[test]
+    fn reports_invalid_months_and_title_mismatches() {
+        let posts = [post_with("胆大党_02.md", "---\ntitle: 胆大党 第3集\ntags: [\"202513\"]\n---\n正文\n")];
+        let report = lint(&posts, &Overrides::default());
+        assert_eq!(codes(&report), ["title-mismatch", "invalid-date"]);
+        assert_eq!((report.errors, report.warnings), (1, 1));
+    }
+}
@@ -1,197 +1,329 @@
 //! Rust program to clone/pull a repo, extract info from markdown files, and save to SQLite.
 
-use std::fs;
-use std::path::Path;
-use std::process::Command;
-use regex::Regex;
-use rusqlite::{params, Connection, Result};
-
-// Helper function to clean series names by removing episode numbers
-fn clean_series_name(name: &str) -> String {
-    if let Some(last_space_idx) = name.rfind(' ') {
-        let (base_name, episode_part) = name.split_at(last_space_idx);
-        let ep_part = episode_part.trim();
-        if ep_part.chars().all(|c| c.is_ascii_digit() || c == '.') {
-            return base_name.trim().to_string();
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rusqlite::params;
+
+use eplot_data_compiler::catalog::Catalog;
+use eplot_data_compiler::compile::{compile_atomic, CompileOptions};
+use eplot_data_compiler::feed::{self, FeedOptions};
+use eplot_data_compiler::git::{self, SyncOptions};
+use eplot_data_compiler::history::file_history;
+use eplot_data_compiler::lint::lint;
+use eplot_data_compiler::overrides::{self, Overrides};
+use eplot_data_compiler::source::{load_posts, Post};
+use eplot_data_compiler::spreadsheet::{self, SheetFormat};
+use eplot_data_compiler::{db, json, Error, Result};
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Compile the eplot blog into a SQLite database",
+    after_help = "With no command, runs `sync` followed by `compile` (just `compile` with --source)."
+)]
+struct Cli {
+    #[command(flatten)]
+    opts: Options,
+    #[command(subcommand)]
+    command: Option<Cmd>,
+}
+
+#[derive(Args)]
+struct Options {
+    /// Git URL of the eplot blog repository, or the path of a local (bare) repository
+    #[arg(long, global = true, default_value = "https://github.com/sudoghut/eplot")]
+    repo_url: String,
+    /// Branch, tag or commit to check out when syncing [default: the remote's default branch]
+    #[arg(long, global = true)]
+    git_ref: Option<String>,
+    /// Fetch only this many commits of history when syncing over the network
+    #[arg(long, global = true)]
+    depth: Option<i32>,
+    /// Local checkout of the blog repository
+    #[arg(long, global = true, default_value = "eplot")]
+    repo_dir: PathBuf,
+    /// Directory holding the posts, relative to the checkout
+    #[arg(long, global = true, default_value = "src/content/blog")]
+    blog_subdir: PathBuf,
+    /// SQLite database to write
+    #[arg(long, global = true, default_value = "data.db")]
+    db: PathBuf,
+    /// Read posts from this directory (its --blog-subdir, if it has one) or .tar/.tar.gz/.tgz/.zip archive
+    /// instead of the checkout; git is never run
+    #[arg(long, global = true)]
+    source: Option<PathBuf>,
+    /// Series alias and override file [default: series-overrides.yaml, if present]
+    #[arg(long, global = true)]
+    overrides: Option<PathBuf>,
+}
+
+impl Options {
+    fn load_posts(&self) -> Result<Vec<Post>> {
+        match &self.source {
+            Some(source) => load_posts(source, &self.blog_subdir),
+            None => load_posts(&self.repo_dir.join(&self.blog_subdir), &self.blog_subdir),
         }
     }
-    name.to_string()
+
+    fn load_overrides(&self) -> Result<Overrides> {
+        match &self.overrides {
+            Some(path) => Overrides::load(path),
+            None if Path::new(overrides::DEFAULT_PATH).exists() => Overrides::load(Path::new(overrides::DEFAULT_PATH)),
+            None => Ok(Overrides::default()),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Clone or fetch the blog repository and check out --git-ref
+    Sync,
+    /// Compile the checked-out posts into the database
+    Compile {
+        /// Rebuild every episode instead of only changed files
+        #[arg(long)]
+        full: bool,
+        /// Fail instead of warning on duplicate, missing or outlying episode numbers
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Report data-quality problems in the posts without writing the database
+    Check {
+        #[arg(long, value_enum, default_value_t = Format::Human)]
+        format: Format,
+    },
+    /// Write the compiled catalog somewhere else
+    #[command(subcommand)]
+    Export(ExportCmd),
+    /// Layer corrections from elsewhere over the compiled catalog
+    #[command(subcommand)]
+    Import(ImportCmd),
+}
+
+#[derive(Subcommand)]
+enum ImportCmd {
+    /// Record the corrected cells of an exported series_data or ep_data sheet as edits
+    /// that every compile applies over the markdown
+    Csv {
+        /// The corrected `.csv` or `.tsv` sheet
+        file: PathBuf,
+    },
 }
 
-fn main() -> Result<()> {
-    let repo_url = "https://github.com/sudoghut/eplot";
-    let repo_dir = "eplot";
-
-    // Clone or pull the repo
-    if Path::new(repo_dir).exists() {
-        println!("Repo exists, running git pull...");
-        let status = Command::new("git")
-            .arg("-C")
-            .arg(repo_dir)
-            .arg("pull")
-            .status()
-            .expect("Failed to run git pull");
-        if !status.success() {
-            eprintln!("git pull failed");
-            return Ok(());
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// One `path:line: severity[code]: message` line per problem
+    Human,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum ExportCmd {
+    /// Copy the compiled database to a standalone SQLite file
+    Sqlite {
+        /// File to create; must not already exist
+        out: PathBuf,
+    },
+    /// Write each compiled table to a CSV (or TSV) file that is safe to open in Excel
+    Csv {
+        /// Directory to write `<table>.csv` files into
+        out: PathBuf,
+        /// Write tab-separated `.tsv` files instead
+        #[arg(long)]
+        tsv: bool,
+        /// Start each file with a UTF-8 byte-order mark, for Excel
+        #[arg(long)]
+        bom: bool,
+    },
+    /// Write Atom feeds of recently added or changed episodes, site-wide and per series
+    Feed {
+        /// Directory to write `atom.xml` and `series/<id>.atom.xml` into
+        out: PathBuf,
+        /// Root URL of the blog the entries link to
+        #[arg(long)]
+        site_url: String,
+        /// Most entries per feed
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        /// Also write RSS 2.0 feeds (`rss.xml`, `series/<id>.rss.xml`)
+        #[arg(long)]
+        rss: bool,
+    },
+    /// Write the compiled catalog as versioned JSON files for static sites
+    Json {
+        /// Directory to write `index.json`, `seasons.json` and `series/<id>.json` into
+        out: PathBuf,
+    },
+}
+
+fn sync(opts: &Options) -> Result<()> {
+    if opts.source.is_some() {
+        return Err(Error::Git("nothing to sync when --source is given".to_string()));
+    }
+    let synced = git::sync(&SyncOptions {
+        url: opts.repo_url.clone(),
+        dir: opts.repo_dir.clone(),
+        reference: opts.git_ref.clone(),
+        depth: opts.depth,
+    })?;
+    if synced.depth_ignored {
+        eprintln!("warning: --depth is ignored for local repositories");
+    }
+    let action = if synced.cloned { "Cloned" } else { "Fetched" };
+    println!("{} {}, checked out eplot {}", action, opts.repo_url, synced.commit);
+    Ok(())
+}
+
+fn print_migrations(migrated: &[(i64, &str)]) {
+    for (version, description) in migrated {
+        println!("Migrated database to version {}: {}", version, description);
+    }
+}
+
+fn run_compile(opts: &Options, full: bool, strict: bool) -> Result<()> {
+    let posts = opts.load_posts()?;
+    // Local sources have no commit, so every file is hashed and there is no history
+    let (commit, history) = match opts.source {
+        Some(_) => (None, None),
+        None => {
+            let history = file_history(&opts.repo_dir, &opts.blog_subdir)
+                .map_err(|err| eprintln!("warning: no commit history: {}", err))
+                .ok();
+            (git::head_commit(&opts.repo_dir), history)
         }
-    } else {
-        println!("Cloning repo...");
-        let status = Command::new("git")
-            .arg("clone")
-            .arg(repo_url)
-            .status()
-            .expect("Failed to run git clone");
-        if !status.success() {
-            eprintln!("git clone failed");
-            return Ok(());
+    };
+    let options = CompileOptions {
+        commit,
+        full,
+        overrides: opts.load_overrides()?,
+        strict,
+        history,
+    };
+    match compile_atomic(&opts.db, &posts, &options)? {
+        None => println!("Already compiled eplot {}, nothing to do.", options.commit.unwrap_or_default()),
+        Some(stats) => {
+            print_migrations(&stats.migrated);
+            if stats.rebuilt {
+                println!("Rebuilt all episodes.");
+            }
+            for (path, err) in &stats.errors {
+                eprintln!("{}: {}", path.display(), err);
+            }
+            for (alias, canonical) in &stats.unused_aliases {
+                eprintln!("warning: alias {} of {} matches no episode", alias, canonical);
+            }
+            for (series, issue) in &stats.coverage_issues {
+                eprintln!("warning: {}: {}", series, issue);
+            }
+            println!(
+                "Done. {} added, {} updated, {} removed, {} unchanged, {} failed.",
+                stats.added, stats.updated, stats.removed, stats.unchanged, stats.failed
+            );
         }
     }
+    Ok(())
+}
 
-    // Find first 5 markdown files in eplot/src/content/blog
-    let blog_dir = format!("{}/src/content/blog", repo_dir);
-    let mut md_files: Vec<_> = fs::read_dir(&blog_dir)
-        .expect("Failed to read blog dir")
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            let path = entry.path();
-            if path.extension()? == "md" { Some(path) } else { None }
-        })
-        .collect();
-    md_files.sort();
-    // md_files.truncate(5);
-
-    // Prepare regex patterns
-    let tag_re = Regex::new(r"tags:\s*\[([^\]]*)\]").unwrap();
-    let yyyymm_re = Regex::new(r"\d{6}").unwrap();
-    let title_re = Regex::new(r#"title:\s*"([^"]*)""#).unwrap();
-
-    // Open SQLite connection
-    let conn = Connection::open("data.db")?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS series_data (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            series_name TEXT UNIQUE,
-            series_year TEXT,
-            series_month TEXT
-        )",
-        [],
-    )?;
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS ep_data (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            ep_name TEXT,
-            ep_num TEXT,
-            ep_year TEXT,
-            ep_month TEXT,
-            series_id INTEGER,
-            abstract TEXT
-        )",
-        [],
-    )?;
-    // Empty the tables before inserting new data
-    conn.execute("DELETE FROM ep_data", [])?;
-    conn.execute("DELETE FROM sqlite_sequence WHERE name='ep_data'", [])?;
-    conn.execute("DELETE FROM series_data", [])?;
-    conn.execute("DELETE FROM sqlite_sequence WHERE name='series_data'", [])?;
-
-    use std::collections::HashMap;
-    // First pass: collect unique series and their year
-    let mut series_map: HashMap<String, (String, String)> = HashMap::new();
-    let mut episodes: Vec<(String, String, String, String, String)> = Vec::new();
-    let desc_re = Regex::new(r#"description:\s*["']?([^"\n']*)"#).unwrap();
-    for path in &md_files {
-        let content = fs::read_to_string(&path).unwrap_or_default();
-        let filename = path.file_name().unwrap().to_string_lossy();
-        let parts: Vec<&str> = filename.split('_').collect();
-        let (series_name, ep_num) = if parts.len() >= 2 {
-            // Get title from markdown or filename
-            let full_title = if let Some(title_caps) = title_re.captures(&content) {
-                title_caps.get(1).map_or(parts[0].to_string(), |m| m.as_str().to_string())
-            } else {
-                parts[0].to_string()
-            };
-            
-            // Clean the series name by removing episode number if present at end
-            let clean_name = clean_series_name(&full_title);
-            
-            (clean_name, parts[1].trim_end_matches(".md").to_string())
-        } else {
-            (filename.to_string(), "".to_string())
-        };
-
-        let mut ep_year = String::new();
-        let mut ep_month = String::new();
-        let mut abstract_text = String::new();
-
-        if let Some(tag_caps) = tag_re.captures(&content) {
-            if let Some(tags_str) = tag_caps.get(1) {
-                if let Some(yyyymm) = yyyymm_re.find(tags_str.as_str()) {
-                    let yyyymm = yyyymm.as_str();
-                    if yyyymm.len() == 6 {
-                        ep_year = yyyymm[0..4].to_string();
-                        ep_month = yyyymm[4..6].to_string();
-                    }
-                }
+// Returns whether no post had errors; warnings alone still pass
+fn check(opts: &Options, format: Format) -> Result<bool> {
+    let report = lint(&opts.load_posts()?, &opts.load_overrides()?);
+    match format {
+        Format::Human => {
+            for problem in &report.problems {
+                println!("{}", problem);
             }
+            println!(
+                "Checked {} files: {} errors, {} warnings.",
+                report.files, report.errors, report.warnings
+            );
         }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report).expect("report serializes")),
+    }
+    Ok(report.errors == 0)
+}
 
-        if let Some(desc_caps) = desc_re.captures(&content) {
-            abstract_text = desc_caps.get(1).map_or(String::new(), |m| m.as_str().trim().to_string());
+fn export(opts: &Options, cmd: &ExportCmd) -> Result<()> {
+    match cmd {
+        ExportCmd::Sqlite { out } => {
+            let conn = db::open(&opts.db)?;
+            // VACUUM INTO writes a consistent, compacted snapshot
+            conn.execute("VACUUM INTO ?1", params![out.to_string_lossy()])?;
+            println!("Exported {} to {}", opts.db.display(), out.display());
         }
-        if abstract_text.is_empty() {
-            // Find content after the second '---'
-            let mut lines = content.lines();
-            let mut dash_count = 0;
-            let mut below = String::new();
-            while let Some(line) = lines.next() {
-                if line.trim() == "---" {
-                    dash_count += 1;
-                    if dash_count == 2 {
-                        break;
-                    }
-                }
-            }
-            // Collect the rest of the lines as content
-            for line in lines {
-                below.push_str(line.trim());
-                below.push(' ');
-            }
-            let below = below.trim();
-            let below_chars: String = below.chars().take(200).collect();
-            if below.chars().count() > 200 {
-                abstract_text = format!("{}...", below_chars);
-            } else {
-                abstract_text = below_chars;
+        ExportCmd::Csv { out, tsv, bom } => {
+            let conn = db::open(&opts.db)?;
+            let format = SheetFormat { tsv: *tsv, bom: *bom };
+            for path in spreadsheet::export(&conn, out, format)? {
+                println!("Wrote {}", path.display());
             }
         }
-
-        // Store series info with clean name
-        series_map.entry(series_name.clone()).or_insert((ep_year.clone(), ep_month.clone()));
-        // Store full episode info with clean name reference
-        episodes.push((series_name.clone(), ep_num, ep_year, ep_month, abstract_text));
+        ExportCmd::Feed {
+            out,
+            site_url,
+            limit,
+            rss,
+        } => {
+            let conn = db::open(&opts.db)?;
+            let options = FeedOptions {
+                site_url: site_url.trim_end_matches('/').to_string(),
+                limit: *limit,
+                rss: *rss,
+            };
+            let written = feed::write_feeds(&conn, out, &options)?;
+            println!("Wrote {} feeds to {}", written, out.display());
+        }
+        ExportCmd::Json { out } => {
+            let catalog = Catalog::load(&db::open(&opts.db)?)?;
+            let written = json::write_catalog(&catalog, out)?;
+            println!("Exported {} series to {}", written, out.display());
+        }
     }
+    Ok(())
+}
 
-    // Insert unique series into series_data (names are already cleaned)
-    for (series_name, (series_year, series_month)) in &series_map {
-        conn.execute(
-            "INSERT INTO series_data (series_name, series_year, series_month) VALUES (?1, ?2, ?3)",
-            params![series_name, series_year, series_month],
-        )?;
+fn import(opts: &Options, cmd: &ImportCmd) -> Result<()> {
+    match cmd {
+        ImportCmd::Csv { file } => {
+            let conn = db::open(&opts.db)?;
+            let stats = spreadsheet::import(&conn, file)?;
+            print_migrations(&stats.migrated);
+            if stats.unknown > 0 {
+                eprintln!("warning: {} rows match no {} id and were skipped", stats.unknown, stats.table);
+            }
+            println!("Recorded {} edits from {} {} rows.", stats.edits, stats.rows, stats.table);
+        }
     }
+    Ok(())
+}
 
-    // Insert episodes with correct series_id
-    for (series_name, ep_num, ep_year, ep_month, abstract_text) in episodes {
-        let clean_name = clean_series_name(&series_name);
-        let mut stmt = conn.prepare("SELECT id FROM series_data WHERE series_name = ?1")?;
-        let series_id: i64 = stmt.query_row(params![clean_name], |row| row.get(0))?;
-        // println!("Inserting: {}, {}, {}, {}, series_id={}, abstract={}", series_name, ep_num, ep_year, ep_month, series_id, abstract_text);
-        conn.execute(
-            "INSERT INTO ep_data (ep_name, ep_num, ep_year, ep_month, series_id, abstract) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![series_name, ep_num, ep_year, ep_month, series_id, abstract_text],
-        )?;
+fn run(cli: &Cli) -> Result<bool> {
+    let opts = &cli.opts;
+    match &cli.command {
+        None => {
+            if opts.source.is_none() {
+                sync(opts)?;
+            }
+            run_compile(opts, false, false)?;
+        }
+        Some(Cmd::Sync) => sync(opts)?,
+        Some(Cmd::Compile { full, strict }) => run_compile(opts, *full, *strict)?,
+        Some(Cmd::Check { format }) => return check(opts, *format),
+        Some(Cmd::Export(cmd)) => export(opts, cmd)?,
+        Some(Cmd::Import(cmd)) => import(opts, cmd)?,
     }
+    Ok(true)
+}
 
-    println!("Done.");
-    Ok(())
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(&cli) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
 }
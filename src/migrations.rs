@@ -0,0 +1,419 @@
+//! Versioned schema migrations, tracked in SQLite's `user_version`.
+//!
+//! Each step moves the database up by one version inside its own transaction.
+//! New schema changes are appended to [`MIGRATIONS`]; released steps never change.
+
+use rusqlite::{Connection, Result as SqlResult};
+
+use crate::error::{Error, Result};
+
+struct Migration {
+    description: &'static str,
+    apply: fn(&Connection) -> SqlResult<()>,
+}
+
+/// Step `n` in this list upgrades a database from version `n` to `n + 1`.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "relational schema with foreign keys and build bookkeeping",
+        apply: v1_initial,
+    },
+    Migration {
+        description: "integer years and months, numeric episode numbers with a display label",
+        apply: v2_typed_numbers,
+    },
+    Migration {
+        description: "full-text search over episode names and abstracts",
+        apply: v3_search_index,
+    },
+    Migration {
+        description: "plot sections parsed out of abstracts",
+        apply: v4_sections,
+    },
+    Migration {
+        description: "full post bodies as markdown, HTML and plain text",
+        apply: v5_bodies,
+    },
+    Migration {
+        description: "normalized tags with date, genre and studio kinds",
+        apply: v6_tags,
+    },
+    Migration {
+        description: "broadcast seasons for series and episodes",
+        apply: v7_seasons,
+    },
+    Migration {
+        description: "series aliases and display names",
+        apply: v8_series_overrides,
+    },
+    Migration {
+        description: "franchises linking the seasons of a show",
+        apply: v9_franchises,
+    },
+    Migration {
+        description: "expected and observed episode counts per series",
+        apply: v10_coverage,
+    },
+    Migration {
+        description: "spreadsheet edits layered over the compiled series and episodes",
+        apply: v11_edits,
+    },
+    Migration {
+        description: "when each episode was first compiled and last changed, for feeds",
+        apply: v12_episode_history,
+    },
+    Migration {
+        description: "first and last commit times and authors of each episode",
+        apply: v13_commit_history,
+    },
+];
+
+/// Schema version this binary writes.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+pub fn schema_version(conn: &Connection) -> SqlResult<i64> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Bring the database up to [`SCHEMA_VERSION`], refusing databases from a newer binary.
+/// Returns the version and description of each step applied.
+pub fn migrate(conn: &Connection) -> Result<Vec<(i64, &'static str)>> {
+    let current = schema_version(conn)?;
+    if current > SCHEMA_VERSION {
+        return Err(Error::SchemaTooNew {
+            found: current,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    let mut applied = Vec::new();
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let tx = conn.unchecked_transaction()?;
+        (migration.apply)(&tx)?;
+        tx.pragma_update(None, "user_version", version as i64 + 1)?;
+        tx.commit()?;
+        applied.push((version as i64 + 1, migration.description));
+    }
+    Ok(applied)
+}
+
+fn v1_initial(conn: &Connection) -> SqlResult<()> {
+    // Unversioned databases predate the constraints, which can't be added in place.
+    // Everything in them is derived from the posts, so drop them and rebuild.
+    conn.execute_batch(
+        "DROP TABLE IF EXISTS source_files;
+        DROP TABLE IF EXISTS build_info;
+        DROP TABLE IF EXISTS ep_data;
+        DROP TABLE IF EXISTS series_data;
+
+        CREATE TABLE series_data (
+            id INTEGER PRIMARY KEY,
+            series_name TEXT NOT NULL UNIQUE,
+            series_year TEXT,
+            series_month TEXT
+        );
+        CREATE INDEX series_data_year_month ON series_data (series_year, series_month);
+
+        CREATE TABLE ep_data (
+            id INTEGER PRIMARY KEY,
+            ep_name TEXT,
+            ep_num TEXT NOT NULL,
+            ep_year TEXT,
+            ep_month TEXT,
+            series_id INTEGER NOT NULL REFERENCES series_data (id),
+            abstract TEXT,
+            UNIQUE (series_id, ep_num)
+        );
+        CREATE INDEX ep_data_year_month ON ep_data (ep_year, ep_month);
+
+        -- Bookkeeping for incremental compiles
+        CREATE TABLE build_info (
+            key TEXT PRIMARY KEY,
+            value TEXT
+        );
+        CREATE TABLE source_files (
+            path TEXT PRIMARY KEY,
+            hash TEXT NOT NULL,
+            ep_id INTEGER NOT NULL UNIQUE REFERENCES ep_data (id) ON DELETE CASCADE
+        );",
+    )
+}
+
+fn v2_typed_numbers(conn: &Connection) -> SqlResult<()> {
+    // Column types can't be changed in place; recreate the compiled tables and
+    // let the next compile refill them.
+    force_rebuild(conn)?;
+    conn.execute_batch(
+        "DROP TABLE ep_data;
+        DROP TABLE series_data;
+
+        CREATE TABLE series_data (
+            id INTEGER PRIMARY KEY,
+            series_name TEXT NOT NULL UNIQUE,
+            series_year INTEGER,
+            series_month INTEGER CHECK (series_month BETWEEN 1 AND 12)
+        );
+        CREATE INDEX series_data_year_month ON series_data (series_year, series_month);
+
+        CREATE TABLE ep_data (
+            id INTEGER PRIMARY KEY,
+            ep_name TEXT,
+            ep_label TEXT NOT NULL,
+            ep_num REAL,
+            ep_year INTEGER,
+            ep_month INTEGER CHECK (ep_month BETWEEN 1 AND 12),
+            series_id INTEGER NOT NULL REFERENCES series_data (id),
+            abstract TEXT,
+            UNIQUE (series_id, ep_label)
+        );
+        CREATE INDEX ep_data_year_month ON ep_data (ep_year, ep_month);
+        CREATE INDEX ep_data_series_num ON ep_data (series_id, ep_num);",
+    )
+}
+
+fn v3_search_index(conn: &Connection) -> SqlResult<()> {
+    // External-content FTS5 table over ep_data, kept in sync by triggers.
+    // The trigram tokenizer needs no word segmentation, so Chinese text matches
+    // any substring of three or more characters:
+    //   SELECT rowid FROM ep_search WHERE ep_search MATCH '王冢真唯' ORDER BY rank
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE ep_search USING fts5(
+            ep_name,
+            abstract,
+            content = 'ep_data',
+            content_rowid = 'id',
+            tokenize = 'trigram'
+        );
+
+        CREATE TRIGGER ep_data_search_insert AFTER INSERT ON ep_data BEGIN
+            INSERT INTO ep_search (rowid, ep_name, abstract) VALUES (new.id, new.ep_name, new.abstract);
+        END;
+        CREATE TRIGGER ep_data_search_delete AFTER DELETE ON ep_data BEGIN
+            INSERT INTO ep_search (ep_search, rowid, ep_name, abstract)
+            VALUES ('delete', old.id, old.ep_name, old.abstract);
+        END;
+        CREATE TRIGGER ep_data_search_update AFTER UPDATE OF ep_name, abstract ON ep_data BEGIN
+            INSERT INTO ep_search (ep_search, rowid, ep_name, abstract)
+            VALUES ('delete', old.id, old.ep_name, old.abstract);
+            INSERT INTO ep_search (rowid, ep_name, abstract) VALUES (new.id, new.ep_name, new.abstract);
+        END;
+
+        INSERT INTO ep_search (ep_search) VALUES ('rebuild');",
+    )
+}
+
+fn v4_sections(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE ep_sections (
+            ep_id INTEGER NOT NULL REFERENCES ep_data (id) ON DELETE CASCADE,
+            position INTEGER NOT NULL,
+            section_title TEXT,
+            subsection_title TEXT,
+            body TEXT NOT NULL,
+            PRIMARY KEY (ep_id, position)
+        );",
+    )?;
+    force_rebuild(conn)
+}
+
+fn v5_bodies(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE ep_body (
+            ep_id INTEGER PRIMARY KEY REFERENCES ep_data (id) ON DELETE CASCADE,
+            markdown TEXT NOT NULL,
+            html TEXT NOT NULL,
+            plain_text TEXT NOT NULL
+        );",
+    )?;
+    force_rebuild(conn)
+}
+
+fn v6_tags(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE tags (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            kind TEXT NOT NULL CHECK (kind IN ('date', 'genre', 'studio', 'other'))
+        );
+        CREATE INDEX tags_kind ON tags (kind);
+
+        CREATE TABLE episode_tags (
+            ep_id INTEGER NOT NULL REFERENCES ep_data (id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags (id),
+            position INTEGER NOT NULL,
+            PRIMARY KEY (ep_id, tag_id)
+        );
+        CREATE INDEX episode_tags_tag ON episode_tags (tag_id);",
+    )?;
+    force_rebuild(conn)
+}
+
+fn v7_seasons(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE seasons (
+            id INTEGER PRIMARY KEY,
+            year INTEGER NOT NULL,
+            quarter INTEGER NOT NULL CHECK (quarter BETWEEN 1 AND 4),
+            start_month INTEGER NOT NULL,
+            name_en TEXT NOT NULL,
+            name_zh TEXT NOT NULL,
+            UNIQUE (year, quarter)
+        );
+
+        ALTER TABLE series_data ADD COLUMN season_id INTEGER REFERENCES seasons (id);
+        ALTER TABLE ep_data ADD COLUMN season_id INTEGER REFERENCES seasons (id);
+        CREATE INDEX series_data_season ON series_data (season_id);
+        CREATE INDEX ep_data_season ON ep_data (season_id);
+
+        -- Every series that premiered in each season
+        CREATE VIEW season_series AS
+            SELECT seasons.id AS season_id, seasons.name_zh, seasons.name_en,
+                   series_data.id AS series_id, series_data.series_name
+            FROM seasons JOIN series_data ON series_data.season_id = seasons.id;",
+    )?;
+    force_rebuild(conn)
+}
+
+fn v8_series_overrides(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "ALTER TABLE series_data ADD COLUMN display_name TEXT;
+        ALTER TABLE ep_data ADD COLUMN raw_series_name TEXT;",
+    )?;
+    force_rebuild(conn)
+}
+
+fn v9_franchises(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE franchises (
+            id INTEGER PRIMARY KEY,
+            franchise_name TEXT NOT NULL UNIQUE
+        );
+
+        ALTER TABLE series_data ADD COLUMN franchise_id INTEGER REFERENCES franchises (id);
+        ALTER TABLE series_data ADD COLUMN season_ordinal INTEGER CHECK (season_ordinal >= 1);
+        CREATE INDEX series_data_franchise ON series_data (franchise_id, season_ordinal);
+
+        -- Every season of each franchise, in order
+        CREATE VIEW franchise_seasons AS
+            SELECT franchises.id AS franchise_id, franchises.franchise_name, series_data.season_ordinal,
+                   series_data.id AS series_id, series_data.series_name
+            FROM franchises JOIN series_data ON series_data.franchise_id = franchises.id;",
+    )?;
+    force_rebuild(conn)
+}
+
+fn v10_coverage(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "ALTER TABLE series_data ADD COLUMN expected_episodes INTEGER;
+        ALTER TABLE series_data ADD COLUMN observed_episodes INTEGER;",
+    )?;
+    force_rebuild(conn)
+}
+
+fn v11_edits(conn: &Connection) -> SqlResult<()> {
+    // Not compiled from the posts, so rebuilds keep it
+    conn.execute_batch(
+        "CREATE TABLE edits (
+            table_name TEXT NOT NULL CHECK (table_name IN ('series_data', 'ep_data')),
+            row_id INTEGER NOT NULL,
+            column_name TEXT NOT NULL,
+            value TEXT,
+            PRIMARY KEY (table_name, row_id, column_name)
+        );",
+    )
+}
+
+fn v12_episode_history(conn: &Connection) -> SqlResult<()> {
+    // Kept across rebuilds, so no foreign key to ep_data; times are Unix seconds
+    conn.execute_batch(
+        "CREATE TABLE episode_history (
+            ep_id INTEGER PRIMARY KEY,
+            hash TEXT NOT NULL,
+            first_seen INTEGER NOT NULL,
+            last_changed INTEGER NOT NULL
+        );
+
+        INSERT INTO episode_history (ep_id, hash, first_seen, last_changed)
+            SELECT ep_id, hash, unixepoch(), unixepoch() FROM source_files;",
+    )
+}
+
+fn v13_commit_history(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch(
+        "ALTER TABLE ep_data ADD COLUMN first_commit_at INTEGER;
+        ALTER TABLE ep_data ADD COLUMN first_author TEXT;
+        ALTER TABLE ep_data ADD COLUMN last_commit_at INTEGER;
+        ALTER TABLE ep_data ADD COLUMN last_author TEXT;
+        CREATE INDEX ep_data_last_commit ON ep_data (last_commit_at);",
+    )?;
+    force_rebuild(conn)
+}
+
+// Forget the build bookkeeping so the next compile rebuilds every episode
+fn force_rebuild(conn: &Connection) -> SqlResult<()> {
+    conn.execute_batch("DELETE FROM source_files; DELETE FROM build_info;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::{compile, CompileOptions};
+    use crate::source::fixtures::post;
+
+    #[test]
+    fn refuses_databases_from_a_newer_binary() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+        let too_new = Error::SchemaTooNew {
+            found: SCHEMA_VERSION + 1,
+            supported: SCHEMA_VERSION,
+        };
+        assert!(matches!(migrate(&conn), Err(err) if err.to_string() == too_new.to_string()));
+    }
+
+    #[test]
+    fn upgrades_the_unversioned_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        // The tables of the original, unversioned data.db
+        conn.execute_batch(
+            "CREATE TABLE series_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                series_name TEXT UNIQUE,
+                series_year TEXT,
+                series_month TEXT
+            );
+            CREATE TABLE ep_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ep_name TEXT,
+                ep_num TEXT,
+                ep_year TEXT,
+                ep_month TEXT,
+                series_id INTEGER,
+                abstract TEXT
+            );
+            INSERT INTO series_data (series_name, series_year, series_month) VALUES ('【我推的孩子】', '2023', '04');
+            INSERT INTO ep_data (ep_name, ep_num, ep_year, ep_month, series_id, abstract)
+                VALUES ('【我推的孩子】', '01', '2023', '04', 1, '');",
+        )
+        .unwrap();
+
+        assert_eq!(migrate(&conn).unwrap().len() as i64, SCHEMA_VERSION);
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        // Running it again has nothing left to do
+        assert_eq!(migrate(&conn).unwrap(), []);
+
+        let stats = compile(&conn, &[post("胆大党_01.md", "202410")], &CompileOptions::default()).unwrap().unwrap();
+        assert_eq!((stats.added, stats.failed), (1, 0));
+        let names: Vec<String> = conn
+            .prepare("SELECT series_name FROM series_data")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<SqlResult<_>>()
+            .unwrap();
+        assert_eq!(names, ["胆大党"]);
+        let violations: i64 =
+            conn.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| row.get(0)).unwrap();
+        assert_eq!(violations, 0);
+    }
+}
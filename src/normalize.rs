@@ -0,0 +1,176 @@
+//! Turn an episode title into the name of its series.
+//!
+//! Posts are titled like `间谍过家家 第二季 第3集`, `XXX EP05`, `XXX #5` or
+//! `XXX 【０５】`. Only the episode marker at the end is removed; season markers
+//! such as `第二季`, `Season 2` or `Ⅲ` stay part of the series name.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// Strategy for deriving a series name from an episode title.
+pub trait NormalizeTitle {
+    fn series_name(&self, title: &str) -> String;
+}
+
+/// The episode-numbering patterns used across the eplot blog.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EplotTitles;
+
+// Arabic digits in either width, optionally with a fractional part for specials
+const NUM: &str = r"[0-9０-９]+(?:[.．][0-9０-９]+)?";
+// Chinese numerals as used in 第十二话
+const ZH_NUM: &str = r"[一二三四五六七八九十百零〇两]+";
+
+fn episode_markers() -> &'static [Regex] {
+    static MARKERS: OnceLock<Vec<Regex>> = OnceLock::new();
+    MARKERS.get_or_init(|| {
+        [
+            // 【第3集】, [EP05], (05), （０５）
+            format!(
+                r"\s*[【\[(（]\s*(?:第\s*)?(?:(?i:ep)\.?|#|＃)?\s*(?:{NUM}|{ZH_NUM})\s*(?:集|话|話|回)?\s*[】\])）]$"
+            ),
+            // 第3集, 第十二话
+            format!(r"\s*第\s*(?:{NUM}|{ZH_NUM})\s*(?:集|话|話|回)$"),
+            // EP05, Ep.5, E05
+            format!(r"(?:^|\s)(?i:ep?)\.?\s*{NUM}$"),
+            // #5, ＃５
+            format!(r"\s*[#＃]\s*{NUM}$"),
+        ]
+        .iter()
+        .map(|pattern| Regex::new(pattern).unwrap())
+        .collect()
+    })
+}
+
+// A bare trailing number, as in `我推的孩子 03`
+fn bare_number() -> &'static Regex {
+    static BARE: OnceLock<Regex> = OnceLock::new();
+    BARE.get_or_init(|| Regex::new(&format!(r"\s+{NUM}$")).unwrap())
+}
+
+// Text that makes a following bare number a season, as in `Season 2` or `Part 2`,
+// unless the season was already numbered as in `2nd Season 25`
+fn is_season_number(before: &str) -> bool {
+    static PREFIX: OnceLock<Regex> = OnceLock::new();
+    static ORDINAL: OnceLock<Regex> = OnceLock::new();
+    let prefix = PREFIX.get_or_init(|| Regex::new(r"(?i)(?:season|part|cour|第)\s*$").unwrap());
+    let ordinal = ORDINAL.get_or_init(|| Regex::new(r"(?i)\d+(?:st|nd|rd|th)\s+season\s*$").unwrap());
+    prefix.is_match(before) && !ordinal.is_match(before)
+}
+
+/// Whole number written in Arabic digits of either width, or in Chinese numerals up to 99.
+pub fn parse_number(text: &str) -> Option<i64> {
+    let digits: String = text
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap(),
+            _ => c,
+        })
+        .collect();
+    if let Ok(num) = digits.parse() {
+        return Some(num);
+    }
+    let digit = |c: char| "零一二三四五六七八九".chars().position(|d| d == c).or((c == '两').then_some(2));
+    let chars: Vec<char> = text.chars().collect();
+    match chars.as_slice() {
+        [c] if *c == '十' => Some(10),
+        [c] => digit(*c).map(|d| d as i64),
+        ['十', ones] => digit(*ones).map(|d| 10 + d as i64),
+        [tens, '十'] => digit(*tens).map(|d| d as i64 * 10),
+        [tens, '十', ones] => Some(digit(*tens)? as i64 * 10 + digit(*ones)? as i64),
+        _ => None,
+    }
+}
+
+const TRAILING_SEPARATORS: &[char] = &['-', '–', '—', '|', ':', '：', '·'];
+
+impl NormalizeTitle for EplotTitles {
+    fn series_name(&self, title: &str) -> String {
+        let title = title.trim();
+        let marker = episode_markers()
+            .iter()
+            .find_map(|marker| marker.find(title))
+            .or_else(|| bare_number().find(title).filter(|found| !is_season_number(&title[..found.start()])));
+        let Some(marker) = marker else {
+            return title.to_string();
+        };
+        let rest = title[..marker.start()].trim_end().trim_end_matches(TRAILING_SEPARATORS).trim_end();
+        // A title that is nothing but an episode number is its own series
+        if rest.is_empty() { title } else { rest }.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_episode_markers() {
+        let cases = [
+            ("间谍过家家 第二季 第3集", "间谍过家家 第二季"),
+            ("葬送的芙莉莲 第十二话", "葬送的芙莉莲"),
+            ("葬送的芙莉莲 第 12 話", "葬送的芙莉莲"),
+            ("药屋少女的呢喃 EP05", "药屋少女的呢喃"),
+            ("药屋少女的呢喃 Ep.5", "药屋少女的呢喃"),
+            ("药屋少女的呢喃 #5", "药屋少女的呢喃"),
+            ("药屋少女的呢喃 ＃５", "药屋少女的呢喃"),
+            ("我推的孩子 【０５】", "我推的孩子"),
+            ("我推的孩子【第3集】", "我推的孩子"),
+            ("我推的孩子 [EP05]", "我推的孩子"),
+            ("我推的孩子 （０５）", "我推的孩子"),
+            ("我推的孩子 03", "我推的孩子"),
+            ("孤独摇滚！ 11.5", "孤独摇滚！"),
+            ("孤独摇滚！ ０３", "孤独摇滚！"),
+            ("孤独摇滚！ - 03", "孤独摇滚！"),
+            ("  胆大党 第3集  ", "胆大党"),
+        ];
+        for (title, series) in cases {
+            assert_eq!(EplotTitles.series_name(title), series, "{}", title);
+        }
+    }
+
+    #[test]
+    fn keeps_season_markers() {
+        let cases = [
+            ("间谍过家家 第二季", "间谍过家家 第二季"),
+            ("灵能百分百 第3季 路人超能100 Ⅲ", "灵能百分百 第3季 路人超能100 Ⅲ"),
+            ("无职转生Ⅱ", "无职转生Ⅱ"),
+            ("Re:Zero Season 2", "Re:Zero Season 2"),
+            ("Re:Zero Season 2 05", "Re:Zero Season 2"),
+            ("Mushoku Tensei Part 2", "Mushoku Tensei Part 2"),
+            ("Overlord 2nd Season 25", "Overlord 2nd Season"),
+            ("咒术回战 第二季 第25集", "咒术回战 第二季"),
+            ("灵能百分百 第3季 路人超能100 Ⅲ 第3集", "灵能百分百 第3季 路人超能100 Ⅲ"),
+        ];
+        for (title, series) in cases {
+            assert_eq!(EplotTitles.series_name(title), series, "{}", title);
+        }
+    }
+
+    #[test]
+    fn keeps_titles_without_a_series_name() {
+        assert_eq!(EplotTitles.series_name("第3集"), "第3集");
+        assert_eq!(EplotTitles.series_name("05"), "05");
+        assert_eq!(EplotTitles.series_name("胆大党"), "胆大党");
+    }
+
+    #[test]
+    fn parses_numbers() {
+        let cases = [
+            ("3", Some(3)),
+            ("１２", Some(12)),
+            ("二", Some(2)),
+            ("两", Some(2)),
+            ("十", Some(10)),
+            ("十二", Some(12)),
+            ("二十", Some(20)),
+            ("二十五", Some(25)),
+            ("一百", None),
+            ("", None),
+        ];
+        for (text, num) in cases {
+            assert_eq!(parse_number(text), num, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Series alias and override map, loaded from `series-overrides.yaml`.
+//!
+//! Each top-level key is a canonical series name. Episodes whose cleaned title
+//! matches one of its `aliases` are grouped under it, and `year`, `month`,
+//! `display_name`, `franchise` and `season` replace what the compiler derived
+//! from the posts.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::ids;
+
+/// File the compiler looks for next to where it runs when `--overrides` isn't given.
+pub const DEFAULT_PATH: &str = "series-overrides.yaml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeriesOverride {
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub year: Option<i64>,
+    pub month: Option<i64>,
+    pub display_name: Option<String>,
+    /// Franchise this series is a season of
+    pub franchise: Option<String>,
+    /// Season ordinal within the franchise
+    pub season: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    series: BTreeMap<String, SeriesOverride>,
+    /// alias -> canonical name
+    aliases: HashMap<String, String>,
+    fingerprint: String,
+}
+
+impl Overrides {
+    pub fn load(path: &Path) -> Result<Overrides> {
+        let text = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+        Overrides::parse(&text).map_err(|message| Error::Overrides {
+            path: PathBuf::from(path),
+            message,
+        })
+    }
+
+    pub fn parse(text: &str) -> std::result::Result<Overrides, String> {
+        let series: BTreeMap<String, SeriesOverride> = if text.trim().is_empty() {
+            BTreeMap::new()
+        } else {
+            serde_yaml::from_str::<Option<_>>(text).map_err(|err| err.to_string())?.unwrap_or_default()
+        };
+        let mut aliases = HashMap::new();
+        for (name, entry) in &series {
+            if let Some(month) = entry.month.filter(|month| !(1..=12).contains(month)) {
+                return Err(format!("{}: month {} is not between 1 and 12", name, month));
+            }
+            if let Some(season) = entry.season.filter(|season| *season < 1) {
+                return Err(format!("{}: season {} must be at least 1", name, season));
+            }
+            for alias in &entry.aliases {
+                if series.contains_key(alias) {
+                    return Err(format!("{}: alias {} is itself a canonical series", name, alias));
+                }
+                if let Some(other) = aliases.insert(alias.clone(), name.clone()) {
+                    return Err(format!("alias {} is listed under both {} and {}", alias, other, name));
+                }
+            }
+        }
+        Ok(Overrides {
+            series,
+            aliases,
+            fingerprint: ids::content_hash(text),
+        })
+    }
+
+    /// Canonical series name for a cleaned title.
+    pub fn canonical<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+
+    pub fn series(&self, canonical: &str) -> Option<&SeriesOverride> {
+        self.series.get(canonical)
+    }
+
+    /// Every `(alias, canonical)` pair, sorted by alias.
+    pub fn aliases(&self) -> Vec<(&str, &str)> {
+        let mut pairs: Vec<_> = self.aliases.iter().map(|(a, c)| (a.as_str(), c.as_str())).collect();
+        pairs.sort();
+        pairs
+    }
+
+    /// Hash of the file contents, recorded so edits trigger a full rebuild.
+    /// Empty when no file was loaded.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+}
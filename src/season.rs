@@ -0,0 +1,43 @@
+//! Broadcast seasons (冬/春/夏/秋番), derived from a series' `YYYYMM` month.
+
+/// One anime broadcast quarter: January, April, July or October.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Season {
+    pub year: i64,
+    /// 1 = winter (Jan), 2 = spring (Apr), 3 = summer (Jul), 4 = fall (Oct)
+    pub quarter: i64,
+}
+
+impl Season {
+    pub fn from_year_month(year: Option<i64>, month: Option<i64>) -> Option<Season> {
+        let (year, month) = (year?, month?);
+        if !(1..=12).contains(&month) {
+            return None;
+        }
+        Some(Season {
+            year,
+            quarter: (month - 1) / 3 + 1,
+        })
+    }
+
+    /// Stable primary key, e.g. `20253` for summer 2025.
+    pub fn id(self) -> i64 {
+        self.year * 10 + self.quarter
+    }
+
+    /// First month of the season, the one the Chinese label is named after
+    pub fn start_month(self) -> i64 {
+        (self.quarter - 1) * 3 + 1
+    }
+
+    /// e.g. `Summer 2025`
+    pub fn name_en(self) -> String {
+        let name = ["Winter", "Spring", "Summer", "Fall"][(self.quarter - 1) as usize];
+        format!("{} {}", name, self.year)
+    }
+
+    /// e.g. `2025年7月番`
+    pub fn name_zh(self) -> String {
+        format!("{}年{}月番", self.year, self.start_month())
+    }
+}
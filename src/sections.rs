@@ -0,0 +1,160 @@
+//! Split episode abstracts into their plot sections.
+//!
+//! Abstracts are written as a run of `章节标题，小节标题：正文。` segments, where
+//! consecutive segments usually share the section title. The body can hold several
+//! sentences, so a sentence only starts a new segment when it opens with a
+//! heading-shaped prefix before its first colon.
+
+/// One `section, subsection: body` segment of an abstract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// `None` only for text before the first heading
+    pub section_title: Option<String>,
+    pub subsection_title: Option<String>,
+    pub body: String,
+}
+
+// Headings are short and never contain sentence punctuation or quotes
+const MAX_HEADING_CHARS: usize = 40;
+
+const OPENING_QUOTES: [char; 5] = ['“', '「', '『', '"', '‘'];
+const CLOSING_QUOTES: [char; 5] = ['”', '」', '』', '"', '’'];
+
+// A colon followed by a whole quoted sentence is speech, as in `她说：“你好。”`, while a
+// heading can still start its body with a quoted name, as in `section6：“姑娘”再次催促……`
+fn is_speech(body: &str) -> bool {
+    let Some(quoted) = body.trim_start().strip_prefix(OPENING_QUOTES) else {
+        return false;
+    };
+    match quoted.find(CLOSING_QUOTES) {
+        Some(close) => quoted[close..]
+            .chars()
+            .skip(1)
+            .all(|c| c.is_whitespace() || matches!(c, '。' | '！' | '？' | '，' | '.' | '!' | '?' | ',')),
+        // Sentences are split at the full stop inside the quote
+        None => true,
+    }
+}
+
+fn split_heading(sentence: &str) -> Option<(&str, &str)> {
+    let colon = sentence.find(['：', ':'])?;
+    let heading = sentence[..colon].trim();
+    let body = &sentence[colon + sentence[colon..].chars().next()?.len_utf8()..];
+    let commas = heading.matches(['，', ',']).count();
+    let plain = !heading.contains(['。', '！', '？', '；', '“', '”', '「', '」', '"']);
+    if heading.is_empty() || heading.chars().count() > MAX_HEADING_CHARS || commas > 1 || !plain || is_speech(body) {
+        return None;
+    }
+    Some((heading, body))
+}
+
+fn sentences(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive(['。', '！', '？']).filter(|s| !s.trim().is_empty())
+}
+
+/// Parse an abstract into its sections. Returns nothing when the text has no headings.
+pub fn parse_sections(text: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    for sentence in sentences(text) {
+        match split_heading(sentence) {
+            Some((heading, body)) => {
+                let (section, subsection, body) = match heading.split_once(['，', ',']) {
+                    Some((section, subsection)) => (section.trim(), Some(subsection.trim().to_string()), body),
+                    // Some posts nest a `小节，要点：` heading under a bare `章节：`
+                    None => match split_heading(body).filter(|(inner, _)| inner.contains(['，', ','])) {
+                        Some((inner, inner_body)) => (heading, Some(inner.to_string()), inner_body),
+                        None => (heading, None, body),
+                    },
+                };
+                sections.push(Section {
+                    section_title: Some(section.to_string()),
+                    subsection_title: subsection,
+                    body: body.trim().to_string(),
+                });
+            }
+            None => match sections.last_mut() {
+                Some(last) => last.body.push_str(sentence.trim()),
+                None => sections.push(Section {
+                    section_title: None,
+                    subsection_title: None,
+                    body: sentence.trim().to_string(),
+                }),
+            },
+        }
+    }
+    if sections.iter().all(|section| section.section_title.is_none()) {
+        return Vec::new();
+    }
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(section: &str, subsection: Option<&str>, body: &str) -> Section {
+        Section {
+            section_title: Some(section.to_string()),
+            subsection_title: subsection.map(str::to_string),
+            body: body.to_string(),
+        }
+    }
+
+    // Abstracts below are excerpts from eplot posts
+
+    #[test]
+    fn splits_numbered_sections() {
+        let text = "section1：后藤双介绍自己，受妈妈委托观察姐姐后藤独最近的异常表现。\
+                    section2：后藤双回忆姐姐过去懒散、只躲在衣橱里弹吉他。她好奇姐姐为何最近敢出门了。";
+        assert_eq!(
+            parse_sections(text),
+            [
+                section("section1", None, "后藤双介绍自己，受妈妈委托观察姐姐后藤独最近的异常表现。"),
+                section("section2", None, "后藤双回忆姐姐过去懒散、只躲在衣橱里弹吉他。她好奇姐姐为何最近敢出门了。"),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_section_and_subsection_headings() {
+        let text = "伊织的奇葩回答与千纱的愤怒，伊织的回答：伊织给出了一个令人费解的回答。千纱对此感到极度愤怒。";
+        assert_eq!(
+            parse_sections(text),
+            [section(
+                "伊织的奇葩回答与千纱的愤怒",
+                Some("伊织的回答"),
+                "伊织给出了一个令人费解的回答。千纱对此感到极度愤怒。"
+            )]
+        );
+    }
+
+    #[test]
+    fn keeps_dialogue_in_the_body() {
+        let text = "电次的梦境与波奇塔的警告：电次做了一个经常做、做完又忘掉的梦。\
+                    波奇塔出现并警告电次：“千万不能开门。” 随后波奇塔问电次更喜欢乡下的老鼠还是城里的老鼠。";
+        assert_eq!(
+            parse_sections(text),
+            [section(
+                "电次的梦境与波奇塔的警告",
+                None,
+                "电次做了一个经常做、做完又忘掉的梦。波奇塔出现并警告电次：“千万不能开门。” 随后波奇塔问电次更喜欢乡下的老鼠还是城里的老鼠。"
+            )]
+        );
+
+        let text = "section10：游戏开始。藤原提出第一个问题：“现在正在恋爱的人？”结果有三人出正面。\
+                    石上提问：“讨厌我的人？”结果有一人出正面。舅舅送给藤宫一句座右铭：“危机即是转机”。";
+        assert_eq!(parse_sections(text).len(), 1);
+    }
+
+    #[test]
+    fn allows_a_quoted_name_at_the_start_of_a_body() {
+        let text = "section6：“姑娘”再次催促礼宾机器人不要乱开门。";
+        assert_eq!(parse_sections(text), [section("section6", None, "“姑娘”再次催促礼宾机器人不要乱开门。")]);
+    }
+
+    #[test]
+    fn returns_nothing_without_headings() {
+        assert!(parse_sections("森西解释道：\"石像人是由泥土和石头构成的魔法生物，不能食用。\"").is_empty());
+        assert!(parse_sections("").is_empty());
+    }
+}
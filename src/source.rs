@@ -0,0 +1,188 @@
+//! Load blog posts from a directory or from a tarball or zip of the blog content.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+use crate::error::{Error, Result};
+
+/// One markdown post, read into memory.
+#[derive(Debug)]
+pub struct Post {
+    /// File name the episode is keyed on, e.g. `spy_01.md`
+    pub name: String,
+    /// Where the post came from, for messages
+    pub path: PathBuf,
+    /// The file contents, or why they could not be read
+    pub content: std::result::Result<String, String>,
+}
+
+/// All `.md` files directly inside `blog_dir`, sorted by path.
+pub fn markdown_files(blog_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut md_files: Vec<_> = fs::read_dir(blog_dir)
+        .map_err(|err| Error::io(blog_dir, err))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            if path.extension()? == "md" { Some(path) } else { None }
+        })
+        .collect();
+    md_files.sort();
+    Ok(md_files)
+}
+
+/// Read every post in `source`, which is either a directory of markdown files or a
+/// `.tar`, `.tar.gz`, `.tgz` or `.zip` archive. Posts come back sorted by name.
+///
+/// A directory that contains `blog_subdir`, such as an eplot checkout, is read from
+/// there; any other directory is read as the blog directory itself. Inside an archive,
+/// only `.md` files under a directory ending in `blog_subdir` are used when there are
+/// any; otherwise every `.md` file in the archive is.
+pub fn load_posts(source: &Path, blog_subdir: &Path) -> Result<Vec<Post>> {
+    if source.is_dir() {
+        let blog_dir = source.join(blog_subdir);
+        let blog_dir = if blog_dir.is_dir() { blog_dir } else { source.to_path_buf() };
+        return Ok(markdown_files(&blog_dir)?
+            .into_iter()
+            .map(|path| Post {
+                name: path.file_name().unwrap().to_string_lossy().into_owned(),
+                content: fs::read_to_string(&path).map_err(|err| format!("failed to read: {}", err)),
+                path,
+            })
+            .collect());
+    }
+
+    let name = source.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let file = File::open(source).map_err(|err| Error::io(source, err))?;
+    let entries = if name.ends_with(".zip") {
+        read_zip(file)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        read_tar(GzDecoder::new(file))
+    } else if name.ends_with(".tar") {
+        read_tar(file)
+    } else {
+        return Err(Error::Archive {
+            path: source.to_path_buf(),
+            message: "expected a directory or a .tar, .tar.gz, .tgz or .zip file".to_string(),
+        });
+    }
+    .map_err(|message| Error::Archive { path: source.to_path_buf(), message })?;
+
+    let in_blog_dir = |path: &Path| path.parent().is_some_and(|parent| parent.ends_with(blog_subdir));
+    let any_in_blog_dir = entries.iter().any(|(path, _)| in_blog_dir(path));
+    let mut posts: Vec<Post> = entries
+        .into_iter()
+        .filter(|(path, _)| !any_in_blog_dir || in_blog_dir(path))
+        .map(|(path, content)| Post {
+            name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            path: source.join(&path),
+            content,
+        })
+        .collect();
+    posts.sort_by(|a, b| a.name.cmp(&b.name));
+    if let Some(pair) = posts.windows(2).find(|pair| pair[0].name == pair[1].name) {
+        return Err(Error::Archive {
+            path: source.to_path_buf(),
+            message: format!("{} appears more than once", pair[0].name),
+        });
+    }
+    Ok(posts)
+}
+
+type Entries = Vec<(PathBuf, std::result::Result<String, String>)>;
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "md")
+}
+
+fn read_entry(mut reader: impl Read) -> std::result::Result<String, String> {
+    let mut content = String::new();
+    reader
+        .read_to_string(&mut content)
+        .map(|_| content)
+        .map_err(|err| format!("failed to read: {}", err))
+}
+
+fn read_tar(reader: impl Read) -> std::result::Result<Entries, String> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = Vec::new();
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        let entry = entry.map_err(|err| err.to_string())?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(|err| err.to_string())?.into_owned();
+        if is_markdown(&path) {
+            entries.push((path, read_entry(entry)));
+        }
+    }
+    Ok(entries)
+}
+
+fn read_zip(reader: impl Read + io::Seek) -> std::result::Result<Entries, String> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|err| err.to_string())?;
+    let mut entries = Vec::new();
+    for idx in 0..archive.len() {
+        let entry = archive.by_index(idx).map_err(|err| err.to_string())?;
+        // enclosed_name rejects absolute paths and `..` components
+        let Some(path) = entry.enclosed_name() else { continue };
+        if entry.is_file() && is_markdown(&path) {
+            entries.push((path, read_entry(entry)));
+        }
+    }
+    Ok(entries)
+}
+
+/// Posts for tests, read from nowhere.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use std::path::PathBuf;
+
+    use super::Post;
+
+    /// A post holding `content`.
+    pub fn post_with(name: &str, content: &str) -> Post {
+        Post {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            content: Ok(content.to_string()),
+        }
+    }
+
+    /// An episode of `title` broadcast in `yyyymm`.
+    pub fn titled_post(name: &str, title: &str, yyyymm: &str) -> Post {
+        post_with(name, &format!("---\ntitle: {}\ntags: [\"{}\"]\n---\n正文\n", title, yyyymm))
+    }
+
+    /// An episode of `胆大党` broadcast in `yyyymm`.
+    pub fn post(name: &str, yyyymm: &str) -> Post {
+        titled_post(name, "胆大党", yyyymm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(posts: &[Post]) -> Vec<&str> {
+        posts.iter().map(|post| post.name.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_a_checkout_from_its_blog_directory() {
+        let tmp = tempfile::tempdir().unwrap();
+        let blog = tmp.path().join("src/content/blog");
+        fs::create_dir_all(&blog).unwrap();
+        fs::write(tmp.path().join("README.md"), "# eplot").unwrap();
+        fs::write(blog.join("spy_02.md"), "two").unwrap();
+        fs::write(blog.join("spy_01.md"), "one").unwrap();
+
+        let subdir = Path::new("src/content/blog");
+        assert_eq!(names(&load_posts(tmp.path(), subdir).unwrap()), ["spy_01.md", "spy_02.md"]);
+        // A blog directory given directly is read as it is
+        assert_eq!(names(&load_posts(&blog, subdir).unwrap()), ["spy_01.md", "spy_02.md"]);
+        assert_eq!(load_posts(&blog, subdir).unwrap()[0].content.as_deref(), Ok("one"));
+    }
+}
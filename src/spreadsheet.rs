@@ -0,0 +1,355 @@
+//! CSV and TSV export of the compiled tables, and import of corrected sheets as edits.
+//!
+//! Exports are UTF-8, optionally with a byte-order mark so Excel detects the encoding
+//! of the Chinese titles. Text cells starting with `=`, `+`, `-` or `@` get a leading
+//! `'` so spreadsheets don't evaluate them as formulas; import strips it again.
+//!
+//! Importing a `series_data` or `ep_data` sheet records every changed cell of the
+//! [`db::EDITABLE`] columns in the `edits` table. Compile applies those edits on top
+//! of the markdown-derived rows every time, so they survive rebuilds.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, OptionalExtension};
+
+use crate::db;
+use crate::error::{Error, Result};
+use crate::migrations;
+
+/// Tables written by [`export`], with the order their rows are written in.
+const TABLES: &[(&str, &str)] = &[
+    ("series_data", "series_name"),
+    ("ep_data", "ep_name, ep_num, ep_label"),
+    ("seasons", "id"),
+    ("franchises", "franchise_name"),
+    ("tags", "name"),
+    ("episode_tags", "ep_id, position"),
+    ("ep_sections", "ep_id, position"),
+];
+
+/// How to write exported sheets.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SheetFormat {
+    /// Tab-separated `.tsv` instead of comma-separated `.csv`
+    pub tsv: bool,
+    /// Start each file with a UTF-8 byte-order mark
+    pub bom: bool,
+}
+
+/// What [`import`] did with a sheet.
+#[derive(Debug, Clone, Default)]
+pub struct ImportStats {
+    pub table: &'static str,
+    pub rows: usize,
+    /// Cells that differ from the database and were recorded as edits
+    pub edits: usize,
+    /// Rows whose id matches nothing in the database
+    pub unknown: usize,
+    /// Schema migrations applied first, as version and description
+    pub migrated: Vec<(i64, &'static str)>,
+}
+
+fn sheet_error(path: &Path, message: impl ToString) -> Error {
+    Error::Spreadsheet {
+        path: PathBuf::from(path),
+        message: message.to_string(),
+    }
+}
+
+fn is_formula(text: &str) -> bool {
+    text.starts_with(['=', '+', '-', '@'])
+}
+
+fn cell(value: Value) -> String {
+    match value {
+        Value::Null | Value::Blob(_) => String::new(),
+        Value::Integer(num) => num.to_string(),
+        Value::Real(num) => num.to_string(),
+        Value::Text(text) => text,
+    }
+}
+
+/// Write each compiled table to `<out_dir>/<table>.csv` (or `.tsv`). Returns the files written.
+pub fn export(conn: &Connection, out_dir: &Path, format: SheetFormat) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(out_dir).map_err(|err| Error::io(out_dir, err))?;
+    let extension = if format.tsv { "tsv" } else { "csv" };
+    let mut written = Vec::new();
+    for &(table, order) in TABLES {
+        let path = out_dir.join(format!("{}.{}", table, extension));
+        let mut file = File::create(&path).map_err(|err| Error::io(&path, err))?;
+        if format.bom {
+            file.write_all("\u{feff}".as_bytes()).map_err(|err| Error::io(&path, err))?;
+        }
+        let mut out = csv::WriterBuilder::new()
+            .delimiter(if format.tsv { b'\t' } else { b',' })
+            .from_writer(file);
+
+        let mut stmt = conn.prepare(&format!("SELECT * FROM {} ORDER BY {}", table, order))?;
+        let columns = stmt.column_count();
+        out.write_record(stmt.column_names()).map_err(|err| sheet_error(&path, err))?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let mut record = Vec::with_capacity(columns);
+            for idx in 0..columns {
+                let value: Value = row.get(idx)?;
+                let guard = matches!(&value, Value::Text(text) if is_formula(text));
+                let text = cell(value);
+                record.push(if guard { format!("'{}", text) } else { text });
+            }
+            out.write_record(&record).map_err(|err| sheet_error(&path, err))?;
+        }
+        out.flush().map_err(|err| Error::io(&path, err))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+// Undo the formula guard added by `export`
+fn unguard(text: &str) -> &str {
+    match text.strip_prefix('\'') {
+        Some(rest) if is_formula(rest) => rest,
+        _ => text,
+    }
+}
+
+// Cells match when their text does, or when both are the same number (`1` and `1.0`)
+fn same_value(current: &str, imported: &str) -> bool {
+    current == imported
+        || matches!((current.parse::<f64>(), imported.parse::<f64>()), (Ok(a), Ok(b)) if a == b)
+}
+
+// Reject values the schema or compile would choke on
+fn check_value(column: &str, value: &str) -> std::result::Result<(), String> {
+    match column {
+        "display_name" => Ok(()),
+        "ep_num" => match value.parse::<f64>() {
+            Ok(num) if num.is_finite() && num >= 0.0 => Ok(()),
+            _ => Err(format!("{} must be a non-negative number, not `{}`", column, value)),
+        },
+        _ => {
+            let num: i64 = value
+                .parse()
+                .map_err(|_| format!("{} must be a whole number, not `{}`", column, value))?;
+            let valid = match column {
+                "series_month" | "ep_month" => (1..=12).contains(&num),
+                "season_ordinal" => num >= 1,
+                _ => true,
+            };
+            if valid {
+                Ok(())
+            } else {
+                Err(format!("{} {} is out of range", column, num))
+            }
+        }
+    }
+}
+
+/// Record the corrected cells of an exported `series_data` or `ep_data` sheet as edits
+/// and apply them to the database.
+pub fn import(conn: &Connection, path: &Path) -> Result<ImportStats> {
+    let text = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    let tsv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tsv"))
+        || text.lines().next().is_some_and(|header| header.contains('\t'));
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(if tsv { b'\t' } else { b',' })
+        .from_reader(text.as_bytes());
+    let headers = reader.headers().map_err(|err| sheet_error(path, err))?.clone();
+    let position = |name: &str| headers.iter().position(|header| header.trim() == name);
+
+    let id_column = position("id").ok_or_else(|| sheet_error(path, "no `id` column"))?;
+    let (table, editable, _) = if position("series_year").is_some() {
+        db::EDITABLE[0]
+    } else if position("ep_label").is_some() {
+        db::EDITABLE[1]
+    } else {
+        return Err(sheet_error(path, "not a series_data or ep_data sheet"));
+    };
+    let editable: Vec<(&str, usize)> = editable
+        .iter()
+        .filter_map(|&column| Some((column, position(column)?)))
+        .collect();
+    if editable.is_empty() {
+        return Err(sheet_error(path, format!("no editable {} columns", table)));
+    }
+    // Excel keeps 15 significant digits and rounds the 16-digit ids of sheets saved in it,
+    // so rows are also found by series name, or by episode name and label
+    let (key_sql, key_columns): (&str, &[&str]) = if table == "series_data" {
+        ("SELECT id FROM series_data WHERE series_name = ?1", &["series_name"])
+    } else {
+        (
+            "SELECT id FROM ep_data WHERE ep_name = ?1
+             AND (ep_label = ?2 OR CAST(ep_label AS REAL) = CAST(?2 AS REAL))
+             ORDER BY ep_label = ?2 DESC LIMIT 1",
+            &["ep_name", "ep_label"],
+        )
+    };
+    let key_fields: Option<Vec<usize>> = key_columns.iter().map(|&column| position(column)).collect();
+
+    let migrated = migrations::migrate(conn)?;
+    let tx = conn.unchecked_transaction()?;
+    let conn = &*tx;
+    let columns: Vec<&str> = editable.iter().map(|&(column, _)| column).collect();
+    let mut current = conn.prepare(&format!("SELECT {} FROM {} WHERE id = ?1", columns.join(", "), table))?;
+    let mut by_key = conn.prepare(key_sql)?;
+    let mut stats = ImportStats {
+        table,
+        migrated,
+        ..Default::default()
+    };
+    for record in reader.records() {
+        let record = record.map_err(|err| sheet_error(path, err))?;
+        let line = record.position().map_or(0, |pos| pos.line());
+        let at_line = |message: String| sheet_error(path, format!("line {}: {}", line, message));
+        let field = |idx: usize| unguard(record.get(idx).unwrap_or_default().trim());
+        stats.rows += 1;
+
+        let mut id = field(id_column).parse::<i64>().ok();
+        if id.is_none() || !current.exists(params_from_iter(id))? {
+            id = match &key_fields {
+                Some(fields) => by_key
+                    .query_row(params_from_iter(fields.iter().map(|&idx| field(idx))), |row| row.get(0))
+                    .optional()?,
+                None => None,
+            };
+        }
+        let Some(id) = id else {
+            stats.unknown += 1;
+            continue;
+        };
+        let mut rows = current.query(params_from_iter([id]))?;
+        let Some(row) = rows.next()? else {
+            stats.unknown += 1;
+            continue;
+        };
+        let mut changes = Vec::new();
+        for (idx, &(column, sheet_idx)) in editable.iter().enumerate() {
+            let was = cell(row.get(idx)?);
+            let value = field(sheet_idx);
+            if same_value(&was, value) {
+                continue;
+            }
+            if !value.is_empty() {
+                check_value(column, value).map_err(at_line)?;
+            }
+            changes.push((column, (!value.is_empty()).then_some(value)));
+        }
+        drop(rows);
+        for (column, value) in changes {
+            db::set_edit(conn, table, id, column, value)?;
+            stats.edits += 1;
+        }
+    }
+    drop((current, by_key));
+    db::apply_edits(conn)?;
+    tx.commit()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use csv::StringRecord;
+
+    use super::*;
+    use crate::compile::{compile, CompileOptions};
+    use crate::source::fixtures::post;
+
+    fn compiled() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        let posts = [post("胆大党_01.md", "202410"), post("胆大党_02.md", "202410")];
+        compile(&conn, &posts, &CompileOptions::default()).unwrap();
+        conn
+    }
+
+    // Header and rows of an exported sheet
+    fn read_sheet(path: &Path, delimiter: u8) -> (StringRecord, Vec<Vec<String>>) {
+        let text = fs::read_to_string(path).unwrap();
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .from_reader(text.trim_start_matches('\u{feff}').as_bytes());
+        let headers = reader.headers().unwrap().clone();
+        let rows = reader.records().map(|record| record.unwrap().iter().map(str::to_string).collect()).collect();
+        (headers, rows)
+    }
+
+    fn write_sheet(path: &Path, delimiter: u8, headers: &StringRecord, rows: &[Vec<String>]) {
+        let mut out = csv::WriterBuilder::new().delimiter(delimiter).from_path(path).unwrap();
+        out.write_record(headers).unwrap();
+        for row in rows {
+            out.write_record(row).unwrap();
+        }
+        out.flush().unwrap();
+    }
+
+    fn column(headers: &StringRecord, name: &str) -> usize {
+        headers.iter().position(|header| header == name).unwrap()
+    }
+
+    fn query<T: rusqlite::types::FromSql>(conn: &Connection, sql: &str) -> T {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn round_trips_edits_through_exported_sheets() {
+        let conn = compiled();
+        let dir = tempfile::tempdir().unwrap();
+        let tsv = SheetFormat { tsv: true, bom: true };
+        export(&conn, dir.path(), tsv).unwrap();
+        let sheet = dir.path().join("series_data.tsv");
+        assert!(fs::read_to_string(&sheet).unwrap().starts_with('\u{feff}'));
+
+        // Saved from Excel: the id turned into a rounded float, and the sheet is tab-separated but not named .tsv
+        let (headers, mut rows) = read_sheet(&sheet, b'\t');
+        let id = &mut rows[0][column(&headers, "id")];
+        *id = format!("{:.14E}", id.parse::<f64>().unwrap());
+        rows[0][column(&headers, "series_month")] = "1".to_string();
+        rows[0][column(&headers, "display_name")] = "'=Dandadan".to_string();
+        let edited = dir.path().join("series_data.txt");
+        write_sheet(&edited, b'\t', &headers, &rows);
+        let stats = import(&conn, &edited).unwrap();
+        assert_eq!((stats.table, stats.rows, stats.edits, stats.unknown), ("series_data", 1, 2, 0));
+        assert_eq!(query::<String>(&conn, "SELECT display_name FROM series_data"), "=Dandadan");
+        assert_eq!(query::<i64>(&conn, "SELECT season_id FROM series_data"), 20241);
+
+        // The guarded formula reads back as the same value, so re-importing changes nothing
+        let csv = SheetFormat { tsv: false, bom: false };
+        let again = dir.path().join("again");
+        export(&conn, &again, csv).unwrap();
+        let (headers, rows) = read_sheet(&again.join("series_data.csv"), b',');
+        assert_eq!(rows[0][column(&headers, "display_name")], "'=Dandadan");
+        assert_eq!(import(&conn, &again.join("series_data.csv")).unwrap().edits, 0);
+
+        // An emptied cell is recorded as a NULL edit, on the episode found by name and label
+        let (headers, mut rows) = read_sheet(&again.join("ep_data.csv"), b',');
+        rows[0][column(&headers, "id")] = "1".to_string();
+        rows[0][column(&headers, "ep_num")].clear();
+        let edited = dir.path().join("ep_data.csv");
+        write_sheet(&edited, b',', &headers, &rows);
+        assert_eq!(import(&conn, &edited).unwrap().edits, 1);
+        let label: String = query(&conn, "SELECT ep_label FROM ep_data WHERE ep_num IS NULL");
+        assert_eq!(label, rows[0][column(&headers, "ep_label")]);
+        let null_edits = "SELECT COUNT(*) FROM edits WHERE column_name = 'ep_num' AND value IS NULL";
+        assert_eq!(query::<i64>(&conn, null_edits), 1);
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let conn = compiled();
+        let dir = tempfile::tempdir().unwrap();
+        export(&conn, dir.path(), SheetFormat::default()).unwrap();
+        let sheet = dir.path().join("ep_data.csv");
+        let (headers, mut rows) = read_sheet(&sheet, b',');
+        rows[1][column(&headers, "ep_year")] = "2025".to_string();
+        rows[1][column(&headers, "ep_month")] = "13".to_string();
+        write_sheet(&sheet, b',', &headers, &rows);
+
+        let err = import(&conn, &sheet).unwrap_err().to_string();
+        assert!(err.ends_with("line 3: ep_month 13 is out of range"), "{}", err);
+        // Nothing from the rejected sheet is kept
+        assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM edits"), 0);
+        assert_eq!(query::<i64>(&conn, "SELECT COUNT(*) FROM ep_data WHERE ep_year = 2025"), 0);
+    }
+}
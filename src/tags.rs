@@ -0,0 +1,72 @@
+//! Classify front-matter tags into dates, genres, studios and everything else.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagKind {
+    /// Broadcast month as `YYYYMM`
+    Date,
+    Genre,
+    Studio,
+    Other,
+}
+
+impl TagKind {
+    /// Value stored in `tags.kind`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TagKind::Date => "date",
+            TagKind::Genre => "genre",
+            TagKind::Studio => "studio",
+            TagKind::Other => "other",
+        }
+    }
+}
+
+const GENRES: &[&str] = &[
+    "恋爱", "爱情", "百合", "后宫", "校园", "日常", "喜剧", "搞笑", "治愈", "奇幻", "异世界", "科幻", "机战", "战斗",
+    "热血", "冒险", "悬疑", "推理", "恐怖", "惊悚", "运动", "音乐", "偶像", "历史", "战争", "美食", "职场", "青春",
+    "剧情", "魔法", "超能力", "转生",
+];
+
+const STUDIOS: &[&str] = &[
+    "京都动画", "京阿尼", "mappa", "ufotable", "a-1 pictures", "cloverworks", "wit studio", "bones", "骨头社",
+    "production i.g", "p.a.works", "shaft", "madhouse", "j.c.staff", "trigger", "动画工房", "doga kobo", "lerche",
+    "8bit", "silver link", "cygamespictures", "science saru", "david production", "kinema citrus", "white fox",
+    "sunrise", "日升", "toei animation", "东映动画", "studio bind", "feel.", "diomedéa", "studio deen", "tms entertainment",
+];
+
+/// The `YYYYMM` broadcast-month tag pattern.
+pub fn is_date_tag(tag: &str) -> bool {
+    static DATE_RE: OnceLock<Regex> = OnceLock::new();
+    DATE_RE
+        .get_or_init(|| Regex::new(r"^[0-9]{4}(0[1-9]|1[0-2])$").unwrap())
+        .is_match(tag)
+}
+
+pub fn classify(tag: &str) -> TagKind {
+    let lower = tag.to_lowercase();
+    if is_date_tag(tag) {
+        TagKind::Date
+    } else if GENRES.contains(&tag) {
+        TagKind::Genre
+    } else if STUDIOS.contains(&lower.as_str()) {
+        TagKind::Studio
+    } else {
+        TagKind::Other
+    }
+}
+
+/// Trimmed, non-empty tags in their original order with duplicates removed.
+pub fn normalize(tags: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !out.iter().any(|seen| seen == tag) {
+            out.push(tag.to_string());
+        }
+    }
+    out
+}